@@ -0,0 +1,104 @@
+use crate::buttons::{
+  ButtonSet,
+  JoystickStates
+};
+use heapless::Vec;
+
+// Maps button chords to application actions (any Copy type, e.g. an enum).
+// An action triggers on the frame its whole chord becomes active; when the
+// chords of several triggered actions overlap only the most specific ones
+// (not contained in another active chord) are reported, so registering
+// CTRL and CTRL + A makes pressing both at once trigger only the second one.
+pub struct ActionMap<A: Copy, const N: usize> {
+  actions: Vec<(ButtonSet, A), N>,
+  states : JoystickStates
+}
+
+impl<A: Copy, const N: usize> Default for ActionMap<A, N> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+#[allow(dead_code)]
+impl<A: Copy, const N: usize> ActionMap<A, N> {
+  pub fn new() -> Self {
+    Self {
+      actions: Vec::new(),
+      states : JoystickStates::from_raw_state(0x0000, 0x0000)
+    }
+  }
+
+  // Fails giving the action back when the N entries are taken
+  pub fn register_action(&mut self, buttons: ButtonSet, action: A) -> Result<(), A> {
+    self.actions.push((buttons, action)).map_err(|(_, action)| action)
+  }
+
+  // With joystick.states(), see Joystick::update
+  pub fn update(&mut self, states: &JoystickStates) {
+    self.states = *states;
+  }
+
+  pub fn triggered_actions(&self) -> impl Iterator<Item = A> + '_ {
+    self.actions
+      .iter()
+      .filter(move |(buttons, _)| self.states.just_combo_pressed(*buttons))
+      .filter(move |(buttons, _)| !self.is_shadowed(*buttons))
+      .map(|&(_, action)| action)
+  }
+
+  // Another registered chord holds every button of this one and more, and is
+  // active now
+  fn is_shadowed(&self, buttons: ButtonSet) -> bool {
+    self.actions.iter().any(|&(other, _)| {
+      other != buttons && other.intersection(buttons) == buttons && self.states.is_combo_active(other)
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::buttons::JoystickButton;
+
+  #[derive(Clone, Copy, Debug, PartialEq)]
+  enum Action {
+    Ctrl,
+    CtrlA,
+    CtrlAB
+  }
+
+  fn chord(buttons: &[JoystickButton]) -> ButtonSet {
+    ButtonSet::from_raw(buttons.iter().fold(0, |bits, &button| bits | button as u16))
+  }
+
+  #[test]
+  fn overlapping_chords_resolve_to_the_most_specific() {
+    use JoystickButton::*;
+
+    let mut map: ActionMap<Action, 3> = ActionMap::new();
+    let mut states = JoystickStates::from_raw_state(0, 0);
+
+    map.register_action(chord(&[CTRL]), Action::Ctrl).unwrap();
+    map.register_action(chord(&[CTRL, A]), Action::CtrlA).unwrap();
+    map.register_action(chord(&[CTRL, A, B]), Action::CtrlAB).unwrap();
+
+    assert_eq!(map.register_action(chord(&[B]), Action::Ctrl), Err(Action::Ctrl));
+
+    states.advance(CTRL as u16 | A as u16);
+    map.update(&states);
+
+    assert!(map.triggered_actions().eq([Action::CtrlA]));
+
+    states.advance(CTRL as u16 | A as u16 | B as u16);
+    map.update(&states);
+
+    assert!(map.triggered_actions().eq([Action::CtrlAB]));
+
+    states.advance(0);
+    states.advance(CTRL as u16);
+    map.update(&states);
+
+    assert!(map.triggered_actions().eq([Action::Ctrl]));
+  }
+}
@@ -0,0 +1,93 @@
+use crate::timing::elapsed;
+
+// Interpolation is done in 16.16 fixed point, so no float support is needed
+const ONE: i64 = 1 << 16;
+
+#[derive(Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub enum AnimationEasing {
+  Linear,
+  EaseInOut // smoothstep: slow start, fast middle, slow end
+}
+
+// Interpolates from start to end over duration ticks (see timing)
+pub struct Animator {
+  duration  : u32,
+  easing    : AnimationEasing,
+  end       : i32,
+  start     : i32,
+  start_tick: u32
+}
+
+#[allow(dead_code)]
+impl Animator {
+  pub fn new(start: i32, end: i32, duration: u32, easing: AnimationEasing, now: u32) -> Self {
+    Self {
+      duration,
+      easing,
+      end,
+      start,
+      start_tick: now
+    }
+  }
+
+  pub fn is_finished(&self, now: u32) -> bool {
+    elapsed(now, self.start_tick) >= self.duration
+  }
+
+  pub fn restart(&mut self, now: u32) {
+    self.start_tick = now;
+  }
+
+  pub fn update(&self, now: u32) -> i32 {
+    let elapsed = elapsed(now, self.start_tick);
+
+    if elapsed >= self.duration {
+      return self.end;
+    }
+
+    let progress = elapsed as i64 * ONE / self.duration as i64;
+
+    let eased = match self.easing {
+      AnimationEasing::Linear    => progress,
+      AnimationEasing::EaseInOut => progress * progress * (3 * ONE - 2 * progress) / ONE / ONE
+    };
+
+    (self.start as i64 + (self.end as i64 - self.start as i64) * eased / ONE) as i32
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn linear_endpoints_and_midpoint() {
+    let animator = Animator::new(100, 300, 1000, AnimationEasing::Linear, 5000);
+
+    assert_eq!(animator.update(5000), 100);
+    assert_eq!(animator.update(5500), 200);
+    assert_eq!(animator.update(6000), 300);
+    assert_eq!(animator.update(9000), 300);
+  }
+
+  // Smoothstep is slower than linear in the first half and halfway at the middle
+  #[test]
+  fn ease_in_out_endpoints_and_midpoint() {
+    let animator = Animator::new(0, 1000, 1000, AnimationEasing::EaseInOut, 0);
+
+    assert_eq!(animator.update(0), 0);
+    assert_eq!(animator.update(500), 500);
+    assert!(animator.update(250) < 250);
+    assert_eq!(animator.update(1000), 1000);
+  }
+
+  #[test]
+  fn counts_down_across_a_tick_wrap() {
+    let animator = Animator::new(300, 100, 1000, AnimationEasing::Linear, u32::MAX - 499);
+
+    assert_eq!(animator.update(0), 200);
+    assert!(!animator.is_finished(0));
+    assert!(animator.is_finished(500));
+  }
+}
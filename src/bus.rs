@@ -0,0 +1,457 @@
+use crate::color::bytes_per_pixel;
+use crate::registers::{
+  blank_command,
+  DisplayColorModeBPP,
+  DisplayCommand
+};
+
+// Framing of the commands and data sent to the panel over CS, DC and the SPI.
+// Display drives its pins and SPI1 through PanelInterface in display.rs; the
+// tests record the same calls
+
+// What PanelBus needs from the pins and the SPI; levels are true for high
+pub trait PanelInterface {
+  fn set_chip_select(&mut self, high: bool);
+
+  fn set_data_command(&mut self, high: bool);
+
+  fn write(&mut self, bytes: &[u8]);
+
+  // Clocks in bytes.len() bytes over the ones passed
+  fn transfer(&mut self, bytes: &mut [u8]);
+
+  // Returns once the last bits written are out of the shift register
+  fn wait_idle(&mut self);
+
+  // Busy waits that many core cycles
+  fn delay_cycles(&mut self, cycles: u32);
+}
+
+// Every transfer asserts CS and sets DC (low for a command, high for data),
+// waiting the settle delays (in core cycles) after each. Inside a batch CS
+// stays low from begin_batch to end_batch and only DC moves
+pub struct PanelBus<I> {
+  batching : bool,
+  cs_delay : u32,
+  dc_delay : u32,
+  interface: I
+}
+
+#[allow(dead_code)]
+impl<I: PanelInterface> PanelBus<I> {
+  pub fn new(interface: I, cs_delay: u32, dc_delay: u32) -> Self {
+    Self {
+      batching : false,
+      cs_delay,
+      dc_delay,
+      interface
+    }
+  }
+
+  pub fn interface(&mut self) -> &mut I {
+    &mut self.interface
+  }
+
+  pub fn command(&mut self, command: u8) {
+    self.select(false);
+
+    self.interface.write(&[command]);
+
+    self.deselect();
+  }
+
+  pub fn data(&mut self, data: &[u8]) {
+    self.select(true);
+
+    self.interface.write(data);
+
+    self.deselect();
+  }
+
+  // CASET, RASET and RAMWR: the pixel data sent next fills the window from
+  // (start_x, start_y) to (end_x, end_y), both included, row by row
+  pub fn window(&mut self, start_x: u16, start_y: u16, end_x: u16, end_y: u16) {
+    self.columns(start_x, end_x);
+
+    self.rows(start_y, end_y);
+
+    self.command(DisplayCommand::RAMWR as u8);
+  }
+
+  pub fn columns(&mut self, start: u16, end: u16) {
+    self.command(DisplayCommand::CASET as u8);
+
+    self.data(&address_range(start, end));
+  }
+
+  pub fn rows(&mut self, start: u16, end: u16) {
+    self.command(DisplayCommand::RASET as u8);
+
+    self.data(&address_range(start, end));
+  }
+
+  // Sends a read command and clocks in the reply. 8-bit reads come straight
+  // after the command byte, no dummy clock is needed in the serial interfaces
+  pub fn read(&mut self, command: u8, data: &mut [u8]) {
+    self.select(false);
+
+    self.interface.write(&[command]);
+
+    self.interface.set_data_command(true);
+
+    self.settle(self.dc_delay);
+
+    data.fill(0);
+
+    self.interface.transfer(data);
+
+    self.deselect();
+  }
+
+  // Keeps CS low from here to end_batch, so the commands and data in between
+  // only toggle DC; the panel doesn't need CS to go high between transfers.
+  // Commands are allowed inside a batch: select drains the SPI before moving
+  // DC, so a command never overlaps the data sent before it
+  pub fn begin_batch(&mut self) {
+    if self.batching {
+      return;
+    }
+
+    self.interface.set_chip_select(false);
+
+    self.settle(self.cs_delay);
+
+    self.batching = true;
+  }
+
+  pub fn end_batch(&mut self) {
+    if !self.batching {
+      return;
+    }
+
+    self.batching = false;
+
+    self.deselect();
+  }
+
+  // Forgets a batch left open without touching CS, for the paths that start
+  // over and frame every transfer again from there
+  pub fn abort_batch(&mut self) {
+    self.batching = false;
+  }
+
+  pub fn is_batching(&self) -> bool {
+    self.batching
+  }
+
+  fn select(&mut self, data: bool) {
+    if !self.batching {
+      self.interface.set_chip_select(false);
+
+      self.settle(self.cs_delay);
+    }
+    else {
+      // In a batch CS doesn't frame the transfers, so DC must not move until
+      // the last bits of the previous one are out of the shift register, or
+      // its tail is latched as the wrong kind (a command as data, or the
+      // other way around)
+      self.interface.wait_idle();
+    }
+
+    self.interface.set_data_command(data);
+
+    self.settle(self.dc_delay);
+  }
+
+  fn deselect(&mut self) {
+    if self.batching {
+      return;
+    }
+
+    self.settle(self.cs_delay);
+
+    self.interface.set_chip_select(true);
+  }
+
+  fn settle(&mut self, cycles: u32) {
+    if cycles > 0 {
+      self.interface.delay_cycles(cycles);
+    }
+  }
+}
+
+// Pixels left to write in the window last opened, counted down by the pixel
+// data sent to it and never below 0
+#[derive(Clone, Copy, Default)]
+pub struct WindowCounter {
+  remaining: u32
+}
+
+#[allow(dead_code)]
+impl WindowCounter {
+  pub fn open(&mut self, start_x: u16, start_y: u16, end_x: u16, end_y: u16) {
+    self.remaining = (end_x - start_x + 1) as u32 * (end_y - start_y + 1) as u32;
+  }
+
+  // length bytes of pixel data in bpp, BPP12 packing two pixels in three
+  pub fn sent(&mut self, bpp: DisplayColorModeBPP, length: usize) {
+    let pixels = match bpp {
+      DisplayColorModeBPP::BPP12 => length * 2 / 3,
+      _                          => length / bytes_per_pixel(bpp).max(1)
+    };
+
+    self.remaining = self.remaining.saturating_sub(pixels as u32);
+  }
+
+  pub fn remaining(&self) -> u32 {
+    self.remaining
+  }
+}
+
+// Hands send data in parts of chunk bytes (0 for all of it at once), calling
+// progress with the bytes sent so far after each
+pub fn send_chunked<S: FnMut(&[u8]), P: FnMut(usize)>(data: &[u8], chunk: usize, mut send: S, mut progress: P) {
+  let chunk = match chunk {
+    0     => data.len().max(1),
+    chunk => chunk
+  };
+  let mut sent = 0;
+
+  for part in data.chunks(chunk) {
+    send(part);
+
+    sent += part.len();
+
+    progress(sent);
+  }
+}
+
+// Takes the output from blanked to blank: sends DISPOFF or DISPON, updates
+// blanked and gives the backlight level to go with it. Nothing is sent (and
+// None given) when the output is already there
+pub fn blank_output<I: PanelInterface>(bus: &mut PanelBus<I>, blanked: &mut bool, blank: bool) -> Option<bool> {
+  let command = blank_command(*blanked, blank)?;
+
+  bus.command(command as u8);
+
+  *blanked = blank;
+
+  Some(!blank)
+}
+
+// CASET/RASET parameters: start and end, high byte first
+fn address_range(start: u16, end: u16) -> [u8; 4] {
+  let [start_hi, start_lo] = start.to_be_bytes();
+  let [end_hi, end_lo] = end.to_be_bytes();
+
+  [start_hi, start_lo, end_hi, end_lo]
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::error::{
+    check_window,
+    DisplayAxis,
+    DisplayError
+  };
+  use crate::geometry::{
+    row_band,
+    Rect
+  };
+
+  #[derive(Debug, PartialEq)]
+  enum Event {
+    ChipSelect(bool),
+    DataCommand(bool),
+    Write(Vec<u8>),
+    Idle
+  }
+
+  // Keeps the pin levels and bytes in the order they were driven
+  #[derive(Default)]
+  struct Recorder {
+    events: Vec<Event>
+  }
+
+  impl PanelInterface for Recorder {
+    fn set_chip_select(&mut self, high: bool) {
+      self.events.push(Event::ChipSelect(high));
+    }
+
+    fn set_data_command(&mut self, high: bool) {
+      self.events.push(Event::DataCommand(high));
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+      self.events.push(Event::Write(bytes.to_vec()));
+    }
+
+    fn transfer(&mut self, _bytes: &mut [u8]) {}
+
+    fn wait_idle(&mut self) {
+      self.events.push(Event::Idle);
+    }
+
+    fn delay_cycles(&mut self, _cycles: u32) {}
+  }
+
+  fn framed(kind: bool, bytes: &[u8]) -> [Event; 4] {
+    [Event::ChipSelect(false), Event::DataCommand(kind), Event::Write(bytes.to_vec()), Event::ChipSelect(true)]
+  }
+
+  // What PanelBus::window sends for the 20x10 window at (10, 300)
+  #[test]
+  fn window_sends_caset_raset_and_ramwr() {
+    let mut bus = PanelBus::new(Recorder::default(), 0, 0);
+
+    bus.window(10, 300, 29, 309);
+
+    let expected: Vec<Event> = [
+      framed(false, &[0x2A]),
+      framed(true, &[0x00, 0x0A, 0x00, 0x1D]),
+      framed(false, &[0x2B]),
+      framed(true, &[0x01, 0x2C, 0x01, 0x35]),
+      framed(false, &[0x2C])
+    ].into_iter().flatten().collect();
+
+    assert_eq!(bus.interface().events, expected);
+  }
+
+  // Display::set_window checks the window first, so one ending past a 240x320
+  // screen sends nothing
+  #[test]
+  fn out_of_range_window_sends_nothing() {
+    let mut bus = PanelBus::new(Recorder::default(), 0, 0);
+
+    let result = check_window(10, 300, 29, 320, 240, 320).map(|()| bus.window(10, 300, 29, 320));
+
+    assert_eq!(result, Err(DisplayError::OutOfBounds { axis: DisplayAxis::Y, value: 320, max: 319 }));
+    assert!(bus.interface().events.is_empty());
+  }
+
+  // CS stays low for the whole batch and DC only moves for the command once
+  // the data before it is out of the SPI
+  #[test]
+  fn command_inside_a_batch_waits_for_the_data() {
+    let mut bus = PanelBus::new(Recorder::default(), 0, 0);
+
+    bus.begin_batch();
+    bus.data(&[0x12, 0x34]);
+    bus.command(0x2C);
+    bus.end_batch();
+
+    assert_eq!(bus.interface().events, [
+      Event::ChipSelect(false),
+      Event::Idle,
+      Event::DataCommand(true),
+      Event::Write(vec![0x12, 0x34]),
+      Event::Idle,
+      Event::DataCommand(false),
+      Event::Write(vec![0x2C]),
+      Event::ChipSelect(true)
+    ]);
+  }
+
+  // The window fill_rows opens for rows 10 to 19 of a 240x320 screen
+  #[test]
+  fn row_band_window_covers_the_whole_width() {
+    let mut bus = PanelBus::new(Recorder::default(), 0, 0);
+    let (start, end) = row_band(10, 19, 240, 320).unwrap().clipped_window(&Rect::new(0, 0, 240, 320)).unwrap();
+
+    bus.window(start.x, start.y, end.x, end.y);
+
+    let expected: Vec<Event> = [
+      framed(false, &[0x2A]),
+      framed(true, &[0x00, 0x00, 0x00, 0xEF]),
+      framed(false, &[0x2B]),
+      framed(true, &[0x00, 0x0A, 0x00, 0x13]),
+      framed(false, &[0x2C])
+    ].into_iter().flatten().collect();
+
+    assert_eq!(bus.interface().events, expected);
+  }
+
+  // send_data_chunked of 10 bytes in chunks of 4 inside its batch
+  #[test]
+  fn chunked_data_reports_the_bytes_sent_so_far() {
+    let mut bus = PanelBus::new(Recorder::default(), 0, 0);
+    let mut counts = Vec::new();
+
+    bus.begin_batch();
+    send_chunked(&[0; 10], 4, |part| bus.data(part), |sent| counts.push(sent));
+    bus.end_batch();
+
+    assert_eq!(counts, [4, 8, 10]);
+
+    let selects = bus.interface().events.iter().filter(|&event| *event == Event::ChipSelect(false)).count();
+    let writes: Vec<usize> = bus.interface().events.iter().filter_map(|event| match event {
+      Event::Write(bytes) => Some(bytes.len()),
+      _                   => None
+    }).collect();
+
+    assert_eq!(selects, 1);
+    assert_eq!(writes, [4, 4, 2]);
+  }
+
+  #[test]
+  fn chunk_0_sends_the_data_whole() {
+    let mut counts = Vec::new();
+
+    send_chunked(&[0; 10], 0, |_| {}, |sent| counts.push(sent));
+    send_chunked(&[], 0, |_| {}, |sent| counts.push(sent));
+
+    assert_eq!(counts, [10]);
+  }
+
+  // blank, blank, unblank, unblank: the repeated calls send nothing and
+  // leave the backlight as it is
+  #[test]
+  fn blank_and_unblank_send_dispoff_then_dispon() {
+    let mut bus = PanelBus::new(Recorder::default(), 0, 0);
+    let mut blanked = false;
+
+    let backlight: Vec<Option<bool>> = [true, true, false, false].into_iter()
+      .map(|blank| blank_output(&mut bus, &mut blanked, blank))
+      .collect();
+
+    assert_eq!(backlight, [Some(false), None, Some(true), None]);
+    assert!(!blanked);
+
+    let expected: Vec<Event> = [framed(false, &[0x28]), framed(false, &[0x29])].into_iter().flatten().collect();
+
+    assert_eq!(bus.interface().events, expected);
+  }
+
+  // A 4x3 BPP16 window filled by two send_data calls
+  #[test]
+  fn window_counts_down_across_sends() {
+    let mut window = WindowCounter::default();
+
+    window.open(10, 20, 13, 22);
+
+    assert_eq!(window.remaining(), 12);
+
+    window.sent(DisplayColorModeBPP::BPP16, 10);
+
+    assert_eq!(window.remaining(), 7);
+
+    window.sent(DisplayColorModeBPP::BPP16, 14);
+
+    assert_eq!(window.remaining(), 0);
+
+    window.sent(DisplayColorModeBPP::BPP16, 2);
+
+    assert_eq!(window.remaining(), 0);
+  }
+
+  #[test]
+  fn bpp12_sends_count_pixel_pairs() {
+    let mut window = WindowCounter::default();
+
+    window.open(0, 0, 9, 0);
+    window.sent(DisplayColorModeBPP::BPP12, 6);
+
+    assert_eq!(window.remaining(), 6);
+  }
+}
@@ -0,0 +1,519 @@
+use core::ops::{
+  BitAnd,
+  BitOr,
+  BitOrAssign
+};
+
+#[repr(u16)]
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+pub enum JoystickButton {
+  A     = 0b0000000000000001, // 0x0001
+  B     = 0b0000000000000010, // 0x0002
+  X     = 0b0000000000000100, // 0x0004
+  Y     = 0b0000000000001000, // 0x0008
+  UP    = 0b0000000000010000, // 0x0010
+  DOWN  = 0b0000000000100000, // 0x0020
+  LEFT  = 0b0000000001000000, // 0x0040
+  RIGHT = 0b0000000010000000, // 0x0080
+  CTRL  = 0b0000000100000000  // 0x0100
+}
+
+pub const BUTTONS: [JoystickButton; 9] = [
+  JoystickButton::A,
+  JoystickButton::B,
+  JoystickButton::X,
+  JoystickButton::Y,
+  JoystickButton::UP,
+  JoystickButton::DOWN,
+  JoystickButton::LEFT,
+  JoystickButton::RIGHT,
+  JoystickButton::CTRL
+];
+
+// Set of buttons as a mask of JoystickButton bits, e.g. JoystickButton::A | JoystickButton::B.
+// from_raw/raw are the escape hatch to plain u16 masks
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ButtonSet(u16);
+
+#[allow(dead_code)]
+impl ButtonSet {
+  pub const ALL  : ButtonSet = ButtonSet(0x01FF);
+  pub const EMPTY: ButtonSet = ButtonSet(0x0000);
+
+  pub fn from_raw(bits: u16) -> Self {
+    Self(bits & Self::ALL.0)
+  }
+
+  pub fn raw(&self) -> u16 {
+    self.0
+  }
+
+  pub fn contains(&self, button: JoystickButton) -> bool {
+    self.0 & button as u16 != 0
+  }
+
+  pub fn intersection(&self, other: ButtonSet) -> ButtonSet {
+    ButtonSet(self.0 & other.0)
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0 == 0
+  }
+
+  pub fn union(&self, other: ButtonSet) -> ButtonSet {
+    ButtonSet(self.0 | other.0)
+  }
+
+  pub fn without(&self, other: ButtonSet) -> ButtonSet {
+    ButtonSet(self.0 & !other.0)
+  }
+}
+
+impl From<JoystickButton> for ButtonSet {
+  fn from(button: JoystickButton) -> Self {
+    ButtonSet(button as u16)
+  }
+}
+
+impl BitAnd for JoystickButton {
+  type Output = ButtonSet;
+
+  fn bitand(self, rhs: Self) -> Self::Output {
+    ButtonSet(self as u16 & rhs as u16)
+  }
+}
+
+impl BitOr for JoystickButton {
+  type Output = ButtonSet;
+
+  fn bitor(self, rhs: Self) -> Self::Output {
+    ButtonSet(self as u16 | rhs as u16)
+  }
+}
+
+impl BitAnd for ButtonSet {
+  type Output = ButtonSet;
+
+  fn bitand(self, rhs: Self) -> Self::Output {
+    self.intersection(rhs)
+  }
+}
+
+impl BitOr for ButtonSet {
+  type Output = ButtonSet;
+
+  fn bitor(self, rhs: Self) -> Self::Output {
+    self.union(rhs)
+  }
+}
+
+impl BitOr<JoystickButton> for ButtonSet {
+  type Output = ButtonSet;
+
+  fn bitor(self, rhs: JoystickButton) -> Self::Output {
+    self.union(rhs.into())
+  }
+}
+
+impl BitOrAssign<JoystickButton> for ButtonSet {
+  fn bitor_assign(&mut self, rhs: JoystickButton) {
+    self.0 |= rhs as u16
+  }
+}
+
+pub type JoystickState = u16;
+
+// Pin-free half of the joystick: a pair of sampled states and every predicate
+// on them, so input logic can be driven from raw bits without HAL pins
+#[derive(Clone, Copy)]
+pub struct JoystickStates {
+  consumed: JoystickState, // active buttons masked out by consume until the next advance
+  current : JoystickState,
+  old     : JoystickState
+}
+
+#[allow(dead_code)]
+impl JoystickStates {
+  pub fn from_raw_state(current: u16, old: u16) -> Self {
+    Self {
+      consumed: 0x0000,
+      current,
+      old
+    }
+  }
+
+  // Takes state as the new current one. Buttons consumed since the last
+  // advance count as active before it, so a consumed hold isn't a new press
+  pub fn advance(&mut self, state: JoystickState) {
+    self.old = self.current | self.consumed;
+    self.current = state;
+    self.consumed = 0x0000;
+  }
+
+  // Marks the button as handled: until the next advance every predicate sees
+  // it inactive (not pressed, held or released), so the UI layers polled after
+  // the one that handled the press ignore it
+  pub fn consume(&mut self, button: JoystickButton) {
+    let button_u16 = button as u16;
+
+    self.consumed |= self.current & button_u16;
+
+    self.current &= !button_u16;
+    self.old &= !button_u16;
+  }
+
+  // Buttons that went from released to pressed, a mask as in raw_state
+  pub fn rising(&self) -> JoystickState {
+    self.current & !self.old
+  }
+
+  pub fn is_active(&self, button: JoystickButton) -> bool {
+    let button_u16 = button as u16;
+
+    self.current & button_u16 == button_u16
+  }
+
+  pub fn just_pressed(&self, button: JoystickButton) -> bool {
+    let button_u16 = button as u16;
+
+    self.old & button_u16 == 0 && self.current & button_u16 == button_u16
+  }
+
+  pub fn just_released(&self, button: JoystickButton) -> bool {
+    let button_u16 = button as u16;
+
+    self.old & button_u16 == button_u16 && self.current & button_u16 == 0
+  }
+
+  pub fn is_hold(&self, button: JoystickButton) -> bool {
+    let button_u16 = button as u16;
+
+    self.old & button_u16 == button_u16 && self.current & button_u16 == button_u16
+  }
+
+  pub fn is_any_active(&self, buttons: Option<ButtonSet>) -> bool {
+    let buttons_value = buttons.unwrap_or(ButtonSet::ALL).raw();
+
+    self.current & buttons_value != 0
+  }
+
+  // Every button of the set is active at once
+  pub fn is_combo_active(&self, buttons: ButtonSet) -> bool {
+    let buttons_value = buttons.raw();
+
+    buttons_value != 0 && self.current & buttons_value == buttons_value
+  }
+
+  // Every button of the set is active now and they weren't all active before
+  pub fn just_combo_pressed(&self, buttons: ButtonSet) -> bool {
+    let buttons_value = buttons.raw();
+
+    self.is_combo_active(buttons) && self.old & buttons_value != buttons_value
+  }
+
+  pub fn active(&self) -> ButtonSet {
+    ButtonSet::from_raw(self.current)
+  }
+
+  // (current, old) as given to from_raw_state: one bit per button as in
+  // JoystickButton (A is bit 0 ... CTRL bit 8), set while pressed
+  pub fn raw_state(&self) -> (u16, u16) {
+    (self.current, self.old)
+  }
+
+  pub fn just_pressed_any(&self) -> bool {
+    self.just_pressed(JoystickButton::A) ||
+    self.just_pressed(JoystickButton::B) ||
+    self.just_pressed(JoystickButton::X) ||
+    self.just_pressed(JoystickButton::Y) ||
+    self.just_pressed(JoystickButton::UP) ||
+    self.just_pressed(JoystickButton::DOWN) ||
+    self.just_pressed(JoystickButton::LEFT) ||
+    self.just_pressed(JoystickButton::RIGHT) ||
+    self.just_pressed(JoystickButton::CTRL)
+  }
+
+  pub fn just_released_any(&self) -> bool {
+    self.just_released(JoystickButton::A) ||
+    self.just_released(JoystickButton::B) ||
+    self.just_released(JoystickButton::X) ||
+    self.just_released(JoystickButton::Y) ||
+    self.just_released(JoystickButton::UP) ||
+    self.just_released(JoystickButton::DOWN) ||
+    self.just_released(JoystickButton::LEFT) ||
+    self.just_released(JoystickButton::RIGHT) ||
+    self.just_released(JoystickButton::CTRL)
+  }
+}
+
+// Auto-fire rates of the buttons, in advance calls (0 when off), and how many
+// each held one has waited since its press or last auto-fire event
+#[derive(Clone, Copy, Default)]
+pub struct JoystickAutofire {
+  rates: [u16; 9],
+  ticks: [u16; 9]
+}
+
+#[allow(dead_code)]
+impl JoystickAutofire {
+  pub fn set(&mut self, button: JoystickButton, rate_ticks: u16) {
+    let index = button_index(button);
+
+    self.rates[index] = rate_ticks;
+    self.ticks[index] = 0;
+  }
+
+  // Call right after each advance: a button held for rate_ticks calls since
+  // its press or last event shows up as just pressed (and not held) again.
+  // Releasing it starts the count over
+  pub fn apply(&mut self, states: &mut JoystickStates) {
+    for button in BUTTONS {
+      let index = button_index(button);
+
+      if self.rates[index] == 0 || !states.is_hold(button) {
+        self.ticks[index] = 0;
+
+        continue;
+      }
+
+      self.ticks[index] += 1;
+
+      if self.ticks[index] >= self.rates[index] {
+        self.ticks[index] = 0;
+
+        states.old &= !(button as u16);
+      }
+    }
+  }
+}
+
+// Called with the button that changed
+pub type JoystickCallback = fn(JoystickButton);
+
+// Press and release callbacks, one of each per button
+#[derive(Clone, Copy, Default)]
+pub struct JoystickCallbacks {
+  press  : [Option<JoystickCallback>; 9],
+  release: [Option<JoystickCallback>; 9]
+}
+
+#[allow(dead_code)]
+impl JoystickCallbacks {
+  pub fn on_press(&mut self, button: JoystickButton, callback: Option<JoystickCallback>) {
+    self.press[button_index(button)] = callback;
+  }
+
+  pub fn on_release(&mut self, button: JoystickButton, callback: Option<JoystickCallback>) {
+    self.release[button_index(button)] = callback;
+  }
+
+  // Calls the callbacks of the buttons just pressed or released in states,
+  // once per advance
+  pub fn dispatch(&self, states: &JoystickStates) {
+    for button in BUTTONS {
+      let index = button_index(button);
+
+      if let Some(callback) = self.press[index] {
+        if states.just_pressed(button) {
+          callback(button);
+        }
+      }
+
+      if let Some(callback) = self.release[index] {
+        if states.just_released(button) {
+          callback(button);
+        }
+      }
+    }
+  }
+}
+
+// Buttons pressed since the last take_just_pressed, accumulated over any
+// number of advances so a press and release between two takes still counts
+#[derive(Clone, Copy, Default)]
+pub struct JoystickPresses {
+  pressed: JoystickState
+}
+
+#[allow(dead_code)]
+impl JoystickPresses {
+  // Call right after each advance (and autofire apply) with the new states
+  pub fn record(&mut self, states: &JoystickStates) {
+    self.pressed |= states.rising();
+  }
+
+  // Bitmask of the buttons pressed since the last call, which is cleared
+  pub fn take_just_pressed(&mut self) -> JoystickState {
+    let pressed = self.pressed;
+
+    self.pressed = 0x0000;
+
+    pressed
+  }
+}
+
+// Position of the button bit, used to index per button arrays
+pub fn button_index(button: JoystickButton) -> usize {
+  (button as u16).trailing_zeros() as usize
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::sync::atomic::{
+    AtomicU32,
+    Ordering
+  };
+
+  #[test]
+  fn press_and_release_between_reads_is_reported() {
+    let mut states = JoystickStates::from_raw_state(0, 0);
+    let mut presses = JoystickPresses::default();
+
+    states.advance(JoystickButton::A as u16);
+    presses.record(&states);
+
+    states.advance(0);
+    presses.record(&states);
+
+    assert!(states.just_released(JoystickButton::A));
+    assert_eq!(presses.take_just_pressed(), JoystickButton::A as u16);
+    assert_eq!(presses.take_just_pressed(), 0);
+  }
+
+  #[test]
+  fn held_button_is_not_pressed_again() {
+    let mut states = JoystickStates::from_raw_state(0, 0);
+
+    states.advance(JoystickButton::B as u16);
+    states.advance(JoystickButton::B as u16);
+
+    assert_eq!(states.rising(), 0);
+    assert!(states.is_hold(JoystickButton::B));
+  }
+
+  #[test]
+  fn consumed_button_is_inactive_for_the_rest_of_the_frame() {
+    let mut states = JoystickStates::from_raw_state(0, 0);
+
+    states.advance(JoystickButton::X as u16);
+
+    assert!(states.just_pressed(JoystickButton::X));
+
+    states.consume(JoystickButton::X);
+
+    assert!(!states.is_active(JoystickButton::X));
+    assert!(!states.just_pressed(JoystickButton::X));
+    assert!(!states.is_hold(JoystickButton::X));
+    assert!(!states.just_released(JoystickButton::X));
+  }
+
+  // Still held on the next frame it's a hold again, not a second press
+  #[test]
+  fn consumed_hold_is_not_pressed_again() {
+    let mut states = JoystickStates::from_raw_state(0, 0);
+
+    states.advance(JoystickButton::X as u16);
+    states.consume(JoystickButton::X);
+    states.advance(JoystickButton::X as u16);
+
+    assert_eq!(states.rising(), 0);
+    assert!(states.is_active(JoystickButton::X));
+    assert!(!states.just_pressed(JoystickButton::X));
+    assert!(states.is_hold(JoystickButton::X));
+
+    states.advance(0);
+    states.advance(JoystickButton::X as u16);
+
+    assert!(states.just_pressed(JoystickButton::X));
+  }
+
+  // Held from the first advance, with auto-fire every 3 calls
+  fn autofire_presses(states: &mut JoystickStates, autofire: &mut JoystickAutofire, held: &[bool]) -> [bool; 10] {
+    let mut pressed = [false; 10];
+
+    for (call, &held) in held.iter().enumerate() {
+      states.advance(if held { JoystickButton::Y as u16 } else { 0 });
+      autofire.apply(states);
+
+      pressed[call] = states.just_pressed(JoystickButton::Y);
+    }
+
+    pressed
+  }
+
+  #[test]
+  fn held_button_fires_every_rate_ticks() {
+    let mut states = JoystickStates::from_raw_state(0, 0);
+    let mut autofire = JoystickAutofire::default();
+
+    autofire.set(JoystickButton::Y, 3);
+
+    let pressed = autofire_presses(&mut states, &mut autofire, &[true; 10]);
+
+    assert_eq!(pressed, [true, false, false, true, false, false, true, false, false, true]);
+  }
+
+  #[test]
+  fn release_restarts_the_autofire_count() {
+    let mut states = JoystickStates::from_raw_state(0, 0);
+    let mut autofire = JoystickAutofire::default();
+
+    autofire.set(JoystickButton::Y, 3);
+
+    let held = [true, true, true, false, true, true, true, true, true, true];
+    let pressed = autofire_presses(&mut states, &mut autofire, &held);
+
+    assert_eq!(pressed, [true, false, false, false, true, false, false, true, false, false]);
+  }
+
+  #[test]
+  fn no_autofire_without_a_rate() {
+    let mut states = JoystickStates::from_raw_state(0, 0);
+    let mut autofire = JoystickAutofire::default();
+
+    let pressed = autofire_presses(&mut states, &mut autofire, &[true; 10]);
+
+    assert_eq!(pressed, [true, false, false, false, false, false, false, false, false, false]);
+  }
+
+  static A_PRESSES: AtomicU32 = AtomicU32::new(0);
+  static A_RELEASES: AtomicU32 = AtomicU32::new(0);
+
+  fn count_a_press(button: JoystickButton) {
+    assert_eq!(button as u16, JoystickButton::A as u16);
+
+    A_PRESSES.fetch_add(1, Ordering::Relaxed);
+  }
+
+  fn count_a_release(button: JoystickButton) {
+    assert_eq!(button as u16, JoystickButton::A as u16);
+
+    A_RELEASES.fetch_add(1, Ordering::Relaxed);
+  }
+
+  // Press, hold, release, stay released, then press and release again, with B
+  // pressed in between without callbacks of its own
+  #[test]
+  fn callbacks_fire_once_per_transition() {
+    let mut states = JoystickStates::from_raw_state(0, 0);
+    let mut callbacks = JoystickCallbacks::default();
+
+    callbacks.on_press(JoystickButton::A, Some(count_a_press));
+    callbacks.on_release(JoystickButton::A, Some(count_a_release));
+
+    let a = JoystickButton::A as u16;
+    let b = JoystickButton::B as u16;
+    let mut counts = [(0, 0); 7];
+
+    for (step, &state) in [a, a, a | b, 0, b, a, 0].iter().enumerate() {
+      states.advance(state);
+      callbacks.dispatch(&states);
+
+      counts[step] = (A_PRESSES.load(Ordering::Relaxed), A_RELEASES.load(Ordering::Relaxed));
+    }
+
+    assert_eq!(counts, [(1, 0), (1, 0), (1, 0), (1, 1), (1, 1), (2, 1), (2, 2)]);
+  }
+}
@@ -0,0 +1,349 @@
+use crate::error::DisplayError;
+use crate::registers::DisplayColorModeBPP;
+
+// Conversions between 0xRRGGBB components and the colors of each color mode,
+// and the bytes those are sent as
+
+// Order of the 4 bit channels inside each byte in BPP12. The datasheet sends
+// the high nibble first (R1G1 B1R2 G2B2) but some panels expect them swapped
+#[derive(Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub enum DisplayNibbleOrder {
+  MsbFirst, // 0xR1G1, 0xB1R2, 0xG2B2
+  LsbFirst  // 0xG1R1, 0xR2B1, 0xB2G2
+}
+
+pub fn rgb888_to_color(bpp: DisplayColorModeBPP, r: u8, g: u8, b: u8) -> u32 {
+  match bpp {
+    DisplayColorModeBPP::BPP12 => ((r as u32 >> 4) << 8) | ((g as u32 >> 4) << 4) | (b as u32 >> 4),
+    DisplayColorModeBPP::BPP16 => ((r as u32 >> 3) << 11) | ((g as u32 >> 2) << 5) | (b as u32 >> 3),
+    _                          => ((r as u32) << 16) | ((g as u32) << 8) | b as u32
+  }
+}
+
+// Inverse of rgb888_to_color, the low bits of each channel repeating the high
+// ones so full intensity maps to 0xFF
+pub fn color_to_rgb888(bpp: DisplayColorModeBPP, color: u32) -> (u8, u8, u8) {
+  match bpp {
+    DisplayColorModeBPP::BPP12 => {
+      let r = ((color >> 8) & 0x0F) as u8;
+      let g = ((color >> 4) & 0x0F) as u8;
+      let b = (color & 0x0F) as u8;
+
+      ((r << 4) | r, (g << 4) | g, (b << 4) | b)
+    },
+    DisplayColorModeBPP::BPP16 => {
+      let r = ((color >> 11) & 0x1F) as u8;
+      let g = ((color >> 5 ) & 0x3F) as u8;
+      let b = (color & 0x1F) as u8;
+
+      ((r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2))
+    },
+    _ => ((color >> 16) as u8, (color >> 8) as u8, color as u8)
+  }
+}
+
+// Luminance with the BT.601 weights (0.299, 0.587, 0.114) in 8 bit fixed point,
+// so a saturated red gives a gray of 76 (0x4C)
+pub fn color_to_grayscale(bpp: DisplayColorModeBPP, color: u32) -> u32 {
+  let (r, g, b) = color_to_rgb888(bpp, color);
+  let gray = ((77 * r as u32 + 150 * g as u32 + 29 * b as u32) >> 8) as u8;
+
+  rgb888_to_color(bpp, gray, gray, gray)
+}
+
+// Bytes of one pixel as sent to the panel, 0 in the modes that don't send
+// whole pixels
+pub fn bytes_per_pixel(bpp: DisplayColorModeBPP) -> usize {
+  match bpp {
+    DisplayColorModeBPP::BPP16  => 2,
+    DisplayColorModeBPP::BPP18  => 3,
+    DisplayColorModeBPP::BPP16M => 3,
+    _                           => 0
+  }
+}
+
+// Bytes of a width x height region, UnsupportedColorMode (whatever the size)
+// in the modes bytes_per_pixel has no size for
+pub fn region_bytes(bpp: DisplayColorModeBPP, width: u16, height: u16) -> Result<usize, DisplayError> {
+  match bytes_per_pixel(bpp) {
+    0               => Err(DisplayError::UnsupportedColorMode),
+    bytes_per_pixel => Ok(width as usize * height as usize * bytes_per_pixel)
+  }
+}
+
+// A BPP12 color (0xRGB) as its (r, g, b) nibbles
+pub fn color_to_nibbles(color: u32) -> (u8, u8, u8) {
+  (((color >> 8) & 0x0F) as u8, ((color >> 4) & 0x0F) as u8, (color & 0x0F) as u8)
+}
+
+// Bytes of one pixel of the color in the color mode and how many are used; in
+// BPP12 the bytes of two pixels of the 0xRGB color
+pub fn pack_color(bpp: DisplayColorModeBPP, order: DisplayNibbleOrder, color: u32) -> ([u8; 3], usize) {
+  match bpp {
+    DisplayColorModeBPP::BPP12 => {
+      let nibbles = color_to_nibbles(color);
+
+      (pack_pixel_pair(order, nibbles, nibbles), 3)
+    },
+    DisplayColorModeBPP::BPP16 => {
+      let color_hi = ((color >> 8) & 0xFF) as u8;
+      let color_lo = (color & 0xFF) as u8;
+
+      ([color_hi, color_lo, 0], 2)
+    },
+    DisplayColorModeBPP::BPP18 | DisplayColorModeBPP::BPP16M => {
+      let color_r = ((color >> 16) & 0xFF) as u8;
+      let color_g = ((color >> 8 ) & 0xFF) as u8;
+      let color_b = (color & 0xFF) as u8;
+
+      ([color_r, color_g, color_b], 3)
+    },
+    _ => ([0, 0, 0], 0)
+  }
+}
+
+// Packs the colors one after the other into out, which must hold them, and
+// returns the bytes used. In BPP12 the colors go in pairs and a lone last one
+// is paired with wrap, the color of the pixel the panel wraps around to
+pub fn pack_row(bpp: DisplayColorModeBPP, order: DisplayNibbleOrder, mut colors: impl Iterator<Item = u32>, wrap: u32, out: &mut [u8]) -> usize {
+  let mut length = 0;
+
+  while let Some(color) = colors.next() {
+    let (pixel, bytes) = match bpp {
+      DisplayColorModeBPP::BPP12 => {
+        let second = colors.next().unwrap_or(wrap);
+
+        (pack_pixel_pair(order, color_to_nibbles(color), color_to_nibbles(second)), 3)
+      },
+      _                          => pack_color(bpp, order, color)
+    };
+
+    out[length..length + bytes].copy_from_slice(&pixel[0..bytes]);
+
+    length += bytes;
+  }
+
+  length
+}
+
+// Packs two 4 bit per channel pixels in the three bytes BPP12 sends them in
+pub fn pack_pixel_pair(order: DisplayNibbleOrder, first: (u8, u8, u8), second: (u8, u8, u8)) -> [u8; 3] {
+  let (r1, g1, b1) = first;
+  let (r2, g2, b2) = second;
+
+  let nibbles = [(r1, g1), (b1, r2), (g2, b2)];
+
+  nibbles.map(|(a, b)| match order {
+    DisplayNibbleOrder::MsbFirst => ((a & 0x0F) << 4) | (b & 0x0F),
+    DisplayNibbleOrder::LsbFirst => ((b & 0x0F) << 4) | (a & 0x0F)
+  })
+}
+
+// Distance between two representable values of each 8 bit channel
+pub fn color_steps(bpp: DisplayColorModeBPP) -> (u16, u16, u16) {
+  match bpp {
+    DisplayColorModeBPP::BPP12  => (16, 16, 16),
+    DisplayColorModeBPP::BPP16  => (8, 4, 8),
+    DisplayColorModeBPP::BPP16M => (8, 4, 8),
+    DisplayColorModeBPP::BPP18  => (4, 4, 4),
+    _                           => (1, 1, 1)
+  }
+}
+
+// Ordered dither of one 8 bit channel at screen pixel (x, y): the 4x4 Bayer
+// threshold adds up to one step, so the value rounds up to the next
+// representable level on the share of the pixels it is past the lower one
+pub fn dither_channel(value: u8, x: u16, y: u16, step: u16) -> u8 {
+  const BAYER: [[u16; 4]; 4] = [
+    [ 0,  8,  2, 10],
+    [12,  4, 14,  6],
+    [ 3, 11,  1,  9],
+    [15,  7, 13,  5]
+  ];
+
+  let threshold = BAYER[(y & 3) as usize][(x & 3) as usize];
+
+  (value as u16 + threshold * step / 16).min(0xFF) as u8
+}
+
+// Complements the colors of whole pixels as they are sent in bpp, keeping the
+// bits the panel ignores clear. BPP12 and unknown modes are left as they are
+pub fn invert_pixels(bpp: DisplayColorModeBPP, pixels: &mut [u8]) {
+  let mask: [u8; 3] = match bpp {
+    DisplayColorModeBPP::BPP16  => [0xFF, 0xFF, 0x00],
+    DisplayColorModeBPP::BPP18  => [0xFC, 0xFC, 0xFC],
+    DisplayColorModeBPP::BPP16M => [0xF8, 0xFC, 0xF8],
+    _                           => return
+  };
+
+  let bytes_per_pixel = bytes_per_pixel(bpp);
+
+  for (i, byte) in pixels.iter_mut().enumerate() {
+    *byte = !*byte & mask[i % bytes_per_pixel];
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // What draw_solid_rect repeats for every pixel of the rect
+  #[test]
+  fn pack_color_in_each_color_mode() {
+    let order = DisplayNibbleOrder::MsbFirst;
+
+    assert_eq!(pack_color(DisplayColorModeBPP::BPP16, order, 0xF81F), ([0xF8, 0x1F, 0x00], 2));
+    assert_eq!(pack_color(DisplayColorModeBPP::BPP18, order, 0xFC8004), ([0xFC, 0x80, 0x04], 3));
+    assert_eq!(pack_color(DisplayColorModeBPP::BPP16M, order, 0x123456), ([0x12, 0x34, 0x56], 3));
+    assert_eq!(pack_color(DisplayColorModeBPP::BPP12, order, 0x123), ([0x12, 0x31, 0x23], 3));
+    assert_eq!(pack_color(DisplayColorModeBPP::UNKNOWN, order, 0xFFFF).1, 0);
+  }
+
+  // BPP12 packs pixel pairs, so it has no whole bytes per pixel
+  #[test]
+  fn bytes_per_pixel_matches_pack_color() {
+    for bpp in [DisplayColorModeBPP::BPP16, DisplayColorModeBPP::BPP18, DisplayColorModeBPP::BPP16M, DisplayColorModeBPP::UNKNOWN] {
+      assert_eq!(bytes_per_pixel(bpp), pack_color(bpp, DisplayNibbleOrder::MsbFirst, 0).1);
+    }
+  }
+
+  // What draw_image_rgb888 sends in BPP16 for a few pixels of an asset
+  #[test]
+  fn rgb888_packs_to_rgb565() {
+    assert_eq!(rgb888_to_color(DisplayColorModeBPP::BPP16, 0xFF, 0x00, 0x00), 0xF800);
+    assert_eq!(rgb888_to_color(DisplayColorModeBPP::BPP16, 0x00, 0xFF, 0x00), 0x07E0);
+    assert_eq!(rgb888_to_color(DisplayColorModeBPP::BPP16, 0x00, 0x00, 0xFF), 0x001F);
+    assert_eq!(rgb888_to_color(DisplayColorModeBPP::BPP16, 0x12, 0x34, 0x56), 0x11AA);
+  }
+
+  // read_region can't convert the 18 bit reads to BPP12 pairs
+  #[test]
+  fn bpp12_regions_are_unsupported() {
+    assert_eq!(region_bytes(DisplayColorModeBPP::BPP12, 4, 2), Err(DisplayError::UnsupportedColorMode));
+    assert_eq!(region_bytes(DisplayColorModeBPP::BPP16, 4, 2), Ok(16));
+    assert_eq!(region_bytes(DisplayColorModeBPP::BPP18, 4, 2), Ok(24));
+  }
+
+  // blit and the other length checks must not pass an empty BPP12 region
+  // with 0 bytes and draw nothing
+  #[test]
+  fn bpp12_is_unsupported_whatever_the_size() {
+    assert_eq!(region_bytes(DisplayColorModeBPP::BPP12, 0, 0), Err(DisplayError::UnsupportedColorMode));
+    assert_eq!(region_bytes(DisplayColorModeBPP::BPP12, 240, 240), Err(DisplayError::UnsupportedColorMode));
+    assert_eq!(region_bytes(DisplayColorModeBPP::UNKNOWN, 1, 1), Err(DisplayError::UnsupportedColorMode));
+    assert_eq!(region_bytes(DisplayColorModeBPP::BPP16M, 0, 5), Ok(0));
+  }
+
+  #[test]
+  fn saturated_red_is_gray_76() {
+    let gray = color_to_grayscale(DisplayColorModeBPP::BPP18, 0xFF0000);
+
+    assert_eq!(gray, 0x4C4C4C);
+    assert_eq!(color_to_grayscale(DisplayColorModeBPP::BPP16, 0xF800), rgb888_to_color(DisplayColorModeBPP::BPP16, 76, 76, 76));
+  }
+
+  #[test]
+  fn pixel_pair_in_both_nibble_orders() {
+    let first = (0x1, 0x2, 0x3);
+    let second = (0x4, 0x5, 0x6);
+
+    assert_eq!(pack_pixel_pair(DisplayNibbleOrder::MsbFirst, first, second), [0x12, 0x34, 0x56]);
+    assert_eq!(pack_pixel_pair(DisplayNibbleOrder::LsbFirst, first, second), [0x21, 0x43, 0x65]);
+  }
+
+  const ROW: [u32; 3] = [0x123456, 0xABCDEF, 0x00FF80];
+
+  #[test]
+  fn three_color_row_in_bpp16() {
+    let out = &mut [0u8; 9];
+    let row = ROW.iter().map(|&color| color & 0xFFFF);
+
+    assert_eq!(pack_row(DisplayColorModeBPP::BPP16, DisplayNibbleOrder::MsbFirst, row, 0, out), 6);
+    assert_eq!(out[..6], [0x34, 0x56, 0xCD, 0xEF, 0xFF, 0x80]);
+  }
+
+  #[test]
+  fn three_color_row_in_bpp18() {
+    let out = &mut [0u8; 9];
+
+    assert_eq!(pack_row(DisplayColorModeBPP::BPP18, DisplayNibbleOrder::MsbFirst, ROW.into_iter(), 0, out), 9);
+    assert_eq!(*out, [0x12, 0x34, 0x56, 0xAB, 0xCD, 0xEF, 0x00, 0xFF, 0x80]);
+  }
+
+  // The lone third pixel is paired with the first one, which it wraps around
+  // to, so a one row window of three repaints that pixel with its own color
+  #[test]
+  fn odd_bpp12_row_pairs_the_last_color_with_wrap() {
+    let out = &mut [0u8; 6];
+    let row = [0x123, 0x456, 0x789];
+
+    assert_eq!(pack_row(DisplayColorModeBPP::BPP12, DisplayNibbleOrder::MsbFirst, row.into_iter(), row[0], out), 6);
+    assert_eq!(*out, [0x12, 0x34, 0x56, 0x78, 0x91, 0x23]);
+  }
+
+  // Red 0x84 sits halfway between the BPP16 levels 0x80 and 0x88, so a flat
+  // dithered region mixes the two
+  #[test]
+  fn dithered_flat_region_mixes_both_levels() {
+    let (step_r, _, _) = color_steps(DisplayColorModeBPP::BPP16);
+    let reds: Vec<u32> = (0..4)
+      .flat_map(|y| (0..4).map(move |x| rgb888_to_color(DisplayColorModeBPP::BPP16, dither_channel(0x84, x, y, step_r), 0, 0)))
+      .collect();
+
+    let low = reds.iter().filter(|&&red| red == 0x8000).count();
+    let high = reds.iter().filter(|&&red| red == 0x8800).count();
+
+    assert_eq!((low, high), (8, 8));
+  }
+
+  #[test]
+  fn dither_stays_below_the_top() {
+    assert_eq!(dither_channel(0xFF, 3, 0, 16), 0xFF);
+    assert_eq!(dither_channel(0x80, 0, 0, 8), 0x80);
+  }
+
+  #[test]
+  fn inverting_red_gives_cyan() {
+    let mut pixels = 0xF800u16.to_be_bytes();
+
+    invert_pixels(DisplayColorModeBPP::BPP16, &mut pixels);
+
+    assert_eq!(u16::from_be_bytes(pixels), 0x07FF);
+  }
+
+  #[test]
+  fn inverting_swaps_black_and_white() {
+    let mut pixels = [0x00, 0x00, 0xFF, 0xFF];
+
+    invert_pixels(DisplayColorModeBPP::BPP16, &mut pixels);
+
+    assert_eq!(pixels, [0xFF, 0xFF, 0x00, 0x00]);
+
+    let mut pixels = [0x00, 0x00, 0x00, 0xFC, 0xFC, 0xFC];
+
+    invert_pixels(DisplayColorModeBPP::BPP18, &mut pixels);
+
+    assert_eq!(pixels, [0xFC, 0xFC, 0xFC, 0x00, 0x00, 0x00]);
+  }
+
+  // BPP12 pixels share bytes, so they're left as they are
+  #[test]
+  fn bpp12_is_not_inverted() {
+    let mut pixels = [0x12, 0x34, 0x56];
+
+    invert_pixels(DisplayColorModeBPP::BPP12, &mut pixels);
+
+    assert_eq!(pixels, [0x12, 0x34, 0x56]);
+  }
+
+  // The low bits the panel drops stay clear in the 3 byte modes
+  #[test]
+  fn inverting_keeps_the_unused_bits_clear() {
+    let mut pixels = [0xF8, 0x00, 0x00, 0x00, 0xFC, 0x00];
+
+    invert_pixels(DisplayColorModeBPP::BPP16M, &mut pixels);
+
+    assert_eq!(pixels, [0x00, 0xFC, 0xF8, 0xF8, 0x00, 0xF8]);
+  }
+}
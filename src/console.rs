@@ -0,0 +1,120 @@
+use crate::display::{
+  Display,
+  DISPLAY_RAM_LINES
+};
+use crate::scroll::ConsoleLines;
+
+// Scrolling log of the last LINES lines of up to COLUMNS characters, drawn at
+// (x, y) with the current text settings. Each println draws the new line and
+// moves the older ones up by one line.
+//
+// With hardware scroll the lines stay where they were drawn in the frame
+// memory, used as a ring, and only the scroll start address moves, so each
+// println redraws a single line. It needs a portrait rotation and the console
+// spanning the whole display width, as the whole band of lines scrolls.
+pub struct Console<const LINES: usize, const COLUMNS: usize> {
+  background_color: u32,
+  hardware_scroll : bool,
+  lines           : ConsoleLines<LINES, COLUMNS>,
+  x               : u16,
+  y               : u16
+}
+
+#[allow(dead_code)]
+impl<const LINES: usize, const COLUMNS: usize> Console<LINES, COLUMNS> {
+  pub fn new(x: u16, y: u16, background_color: u32) -> Self {
+    Self {
+      background_color,
+      hardware_scroll : false,
+      lines           : ConsoleLines::new(),
+      x,
+      y
+    }
+  }
+
+  // Sets the display scroll area to the console lines; the text scale must not
+  // change afterwards
+  pub fn enable_hardware_scroll<const BUFFER_SIZE: usize>(&mut self, display: &mut Display<BUFFER_SIZE>) {
+    self.hardware_scroll = true;
+
+    display.set_scroll_area(self.y, LINES as u16 * display.char_height());
+
+    self.redraw(display);
+  }
+
+  pub fn disable_hardware_scroll<const BUFFER_SIZE: usize>(&mut self, display: &mut Display<BUFFER_SIZE>) {
+    self.hardware_scroll = false;
+
+    display.set_scroll_area(0, DISPLAY_RAM_LINES);
+    display.set_scroll_start(0);
+
+    self.redraw(display);
+  }
+
+  pub fn clear<const BUFFER_SIZE: usize>(&mut self, display: &mut Display<BUFFER_SIZE>) {
+    self.lines.clear();
+
+    self.redraw(display);
+  }
+
+  // Lines longer than COLUMNS are cut and '\n' starts a new line
+  pub fn println<const BUFFER_SIZE: usize>(&mut self, display: &mut Display<BUFFER_SIZE>, text: &str) {
+    for line in text.split('\n') {
+      self.push_line(display, line);
+    }
+  }
+
+  // Draws every line again, e.g. after something was drawn over the console
+  pub fn redraw<const BUFFER_SIZE: usize>(&mut self, display: &mut Display<BUFFER_SIZE>) {
+    if self.hardware_scroll {
+      self.lines.rewind();
+
+      display.set_scroll_start(self.y);
+    }
+
+    for row in 0..LINES {
+      self.draw_row(display, row);
+    }
+  }
+
+  fn push_line<const BUFFER_SIZE: usize>(&mut self, display: &mut Display<BUFFER_SIZE>, line: &str) {
+    let (slot, scrolled) = match self.lines.push(line) {
+      Some(pushed) => pushed,
+      None         => return
+    };
+
+    if !self.hardware_scroll {
+      for row in 0..LINES {
+        self.draw_row(display, row);
+      }
+
+      return;
+    }
+
+    self.draw_slot(display, slot, self.lines.len() - 1);
+
+    if scrolled {
+      display.set_scroll_start(self.y + self.lines.offset() as u16 * display.char_height());
+    }
+  }
+
+  fn draw_row<const BUFFER_SIZE: usize>(&self, display: &mut Display<BUFFER_SIZE>, row: usize) {
+    let slot = match self.hardware_scroll {
+      true  => self.lines.row_slot(row),
+      false => row
+    };
+
+    self.draw_slot(display, slot, row);
+  }
+
+  fn draw_slot<const BUFFER_SIZE: usize>(&self, display: &mut Display<BUFFER_SIZE>, slot: usize, index: usize) {
+    let char_height = display.char_height();
+    let line_y = self.y + slot as u16 * char_height;
+
+    display.draw_solid_rect(self.x, line_y, COLUMNS as u16 * display.char_width(), char_height, self.background_color);
+
+    if let Some(line) = self.lines.line(index) {
+      display.draw_str(self.x, line_y, line);
+    }
+  }
+}
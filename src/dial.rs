@@ -0,0 +1,135 @@
+// Angles of the round widgets (Gauge, Spinner). Degrees as in trig: 0
+// pointing right and growing clockwise on screen
+
+pub const GAUGE_START_DEGREES: i32 = 135;
+pub const GAUGE_SWEEP_DEGREES: i32 = 270;
+
+// Where the filled part of a gauge dial ends for the value (clamped to
+// min..=max), sweeping clockwise from the bottom left
+pub fn gauge_angle(value: u32, min: u32, max: u32) -> i32 {
+  let range = max.saturating_sub(min);
+
+  if range == 0 {
+    return GAUGE_START_DEGREES;
+  }
+
+  let value = value.clamp(min, max) - min;
+
+  GAUGE_START_DEGREES + (GAUGE_SWEEP_DEGREES as i64 * value as i64 / range as i64) as i32
+}
+
+// Most segments of a spinner ring, one per degree
+pub const SPINNER_MAX_SEGMENTS: u16 = 360;
+
+// Segments of a spinner ring, the lit ones moving clockwise one segment per
+// advance. Segment 0 starts at 0 degrees
+#[derive(Clone, Copy)]
+pub struct SpinnerRing {
+  lit     : u16,
+  position: u16, // first lit segment
+  segments: u16
+}
+
+#[allow(dead_code)]
+impl SpinnerRing {
+  // 1..=SPINNER_MAX_SEGMENTS segments, and no more lit ones than segments
+  pub fn new(segments: u16, lit: u16) -> Self {
+    let segments = segments.clamp(1, SPINNER_MAX_SEGMENTS);
+
+    Self {
+      lit     : lit.min(segments),
+      position: 0,
+      segments
+    }
+  }
+
+  // Where the lit segments start
+  pub fn angle(&self) -> i32 {
+    self.segment_degrees(self.position)
+  }
+
+  pub fn is_lit(&self, segment: u16) -> bool {
+    (segment % self.segments + self.segments - self.position) % self.segments < self.lit
+  }
+
+  pub fn segments(&self) -> u16 {
+    self.segments
+  }
+
+  // Start of the segment; segments() gives the end of the last one, 360
+  pub fn segment_degrees(&self, segment: u16) -> i32 {
+    segment as i32 * 360 / self.segments as i32
+  }
+
+  // Moves one segment on, returning the segment turning off and the one
+  // turning on; None with none or all of them lit, as nothing changes
+  pub fn advance(&mut self) -> Option<(u16, u16)> {
+    let changed = match self.lit != 0 && self.lit != self.segments {
+      true  => Some((self.position, (self.position + self.lit) % self.segments)),
+      false => None
+    };
+
+    self.position = (self.position + 1) % self.segments;
+
+    changed
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn mid_range_value_sweeps_half_the_dial() {
+    assert_eq!(gauge_angle(50, 0, 100), GAUGE_START_DEGREES + 135);
+    assert_eq!(gauge_angle(30, 20, 40), 270);
+  }
+
+  #[test]
+  fn values_out_of_range_are_clamped() {
+    assert_eq!(gauge_angle(0, 10, 20), GAUGE_START_DEGREES);
+    assert_eq!(gauge_angle(99, 10, 20), GAUGE_START_DEGREES + GAUGE_SWEEP_DEGREES);
+    assert_eq!(gauge_angle(5, 7, 7), GAUGE_START_DEGREES);
+  }
+
+  #[test]
+  fn advance_moves_the_spinner_one_segment() {
+    let mut ring = SpinnerRing::new(8, 3);
+
+    assert_eq!(ring.angle(), 0);
+    assert!(ring.is_lit(2) && !ring.is_lit(3));
+    assert_eq!(ring.advance(), Some((0, 3)));
+    assert_eq!(ring.angle(), 45);
+    assert!(!ring.is_lit(0) && ring.is_lit(3));
+
+    for _ in 0..6 {
+      ring.advance();
+    }
+
+    assert_eq!(ring.angle(), 315);
+    assert_eq!(ring.advance(), Some((7, 2)));
+    assert_eq!(ring.angle(), 0);
+  }
+
+  #[test]
+  fn fully_lit_ring_changes_nothing() {
+    let mut ring = SpinnerRing::new(4, 9);
+
+    assert_eq!(ring.advance(), None);
+    assert_eq!(ring.angle(), 90);
+    assert_eq!(SpinnerRing::new(0, 0).segments(), 1);
+  }
+
+  #[test]
+  fn segments_are_clamped_to_one_per_degree() {
+    let mut ring = SpinnerRing::new(u16::MAX, 1);
+
+    assert_eq!(ring.segments(), SPINNER_MAX_SEGMENTS);
+    assert_eq!(ring.segment_degrees(359), 359);
+
+    // Segments past the ring wrap around it, 65535 being segment 15
+    assert!(!ring.is_lit(u16::MAX));
+    assert_eq!(ring.advance(), Some((0, 1)));
+    assert!(ring.is_lit(361));
+  }
+}
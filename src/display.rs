@@ -1,21 +1,115 @@
-use core::ops::BitOr;
 use cortex_m::{
-  delay::Delay,
-  prelude::_embedded_hal_blocking_spi_Write
+  prelude::{
+    _embedded_hal_blocking_spi_Transfer,
+    _embedded_hal_blocking_spi_Write
+  }
 };
 use embedded_hal::{
-  digital::v2::OutputPin,
+  blocking::delay::DelayMs,
+  digital::v2::{
+    InputPin,
+    OutputPin
+  },
   spi::Mode
 };
-use crate::font::FONT;
+use crate::bus::{
+  blank_output,
+  PanelBus,
+  PanelInterface,
+  send_chunked,
+  WindowCounter
+};
+use crate::color::{
+  bytes_per_pixel,
+  color_steps,
+  color_to_grayscale,
+  dither_channel,
+  invert_pixels,
+  pack_color,
+  pack_row,
+  region_bytes,
+  rgb888_to_color
+};
+pub use crate::color::DisplayNibbleOrder;
+pub use crate::error::{
+  DisplayAxis,
+  DisplayError
+};
+use crate::error::{
+  check_range,
+  check_window
+};
+use crate::fmt::{
+  format_u32_into,
+  format_u32_padded_into,
+  U32_MAX_LENGTH
+};
+use crate::font;
+use crate::framebuffer::{
+  DrawTarget,
+  ScreenBuffer
+};
+use crate::geometry::{
+  bar_chart_bar,
+  Point,
+  Rect,
+  row_band,
+  Size
+};
+use crate::image::{
+  bitmap_bit,
+  bitmap_row_bytes,
+  changed_runs,
+  scan_region,
+  stream_rows
+};
+use crate::palette::Palette;
+use crate::pattern::{
+  pattern_bit,
+  pattern_runs
+};
+pub use crate::registers::{
+  color_mode_bits,
+  configured_bpp,
+  DisplayInitStep,
+  DISPLAY_INIT_TABLE,
+  DisplayColorMode,
+  DisplayColorModeBPP,
+  DisplayCommand,
+  DisplayRotation,
+  gate_control,
+  gate_timing_adjustment,
+  Madctl
+};
+use crate::sprite::Sprite;
+pub use crate::text::{
+  DisplayTextAlign,
+  DisplayTextAnchor,
+  DisplayTextDirection,
+  DISPLAY_TEXT_MAX_OUTLINE,
+  DISPLAY_TEXT_MAX_PIXEL_SIZE
+};
+use crate::text::{
+  draw_bytes,
+  draw_glyph,
+  saturating_count,
+  wrap_paragraph,
+  TextScale,
+  TextStyle
+};
+use crate::trig::sector_contains;
 use fugit::HertzU32;
-use heapless::String;
+use heapless::{
+  String,
+  Vec
+};
 use rp2040_hal::{
   Spi,
   gpio::{
     FunctionSpi,
     Pin,
     PinId,
+    PullDownInput,
     PushPullOutput,
     bank0::{
       Gpio8,
@@ -23,7 +117,9 @@ use rp2040_hal::{
       Gpio10,
       Gpio11,
       Gpio12,
-      Gpio13
+      Gpio13,
+      Gpio22,
+      Gpio28
     }
   },
   pac::{
@@ -33,183 +129,189 @@ use rp2040_hal::{
   spi::Enabled
 };
 
-// const BUFFER_SIZE: u16 = 512;
+// Size in bytes of the stack buffer used to stream repeated pixels in fills.
+// Bigger buffers mean fewer SPI transactions (faster fills) but cost that much
+// stack on every fill; it must hold at least one pixel (3 bytes).
+pub const DISPLAY_BUFFER_SIZE: usize = 512;
 
-// https://datasheets.raspberrypi.com/rp2040/rp2040-datasheet.pdf
-// https://www.rhydolabz.com/documents/33/ST7789.pdf
-#[repr(u8)]
-#[allow(dead_code)]
-pub enum DisplayCommand {
-  NOP        = 0x00, // Do nothing
-  SWRESET    = 0x01, // Software reset
-  RDDID      = 0x04, // Read display ID
-  RDDST      = 0x09, // Read display status
-  RDDPM      = 0x0A, // Read display power mode
-  RDDMADCTL  = 0x0B, // Read display MADCTL
-  RDDCOLMOD  = 0x0C, // Read display pixel format
-  RDDIM      = 0x0D, // Read display image format
-  RDDSM      = 0x0E, // Read display signal mode
-  RDDSDR     = 0x0F, // Read display self-diagnostic result
-  SLPIN      = 0x10, // Sleep in
-  SLPOUT     = 0x11, // Sleep out
-  PTLON      = 0x12, // Partial display mode on
-  NORON      = 0x13, // Normal display mode on
-  INVOFF     = 0x20, // Display inversion off
-  INVON      = 0x21, // Display inversion on
-  GAMSET     = 0x26, // Gamma set
-  DISPOFF    = 0x28, // Display off
-  DISPON     = 0x29, // Display on
-  CASET      = 0x2A, // Column address set
-  RASET      = 0x2B, // Row address set
-  RAMWR      = 0x2C, // Memory write
-  RAMRD      = 0x2E, // Memory read
-  PTLAR      = 0x30, // Partial area
-  VSCRDEF    = 0x33, // Vertical scrolling definition
-  TEOFF      = 0x34, // Tearing effect line off
-  TEON       = 0x35, // Tearing effect line on
-  MADCTL     = 0x36, // Memory data access control
-  VSCSAD     = 0x37, // Vertical scroll start address of RAM
-  IDMOFF     = 0x38, // Idle mode off
-  IDMON      = 0x39, // Idle mode on
-  COLMOD     = 0x3A, // Interface pixel format
-  WRMEMC     = 0x3C, // Write memory continue
-  RDMEMC     = 0x3E, // Read memory continue
-  STE        = 0x44, // Set tear scanline
-  GSCAN      = 0x45, // Get scanline
-  WRDISBV    = 0x51, // Write display brightness
-  RDDISBV    = 0x52, // Read display brightness value
-  WRCTRLD    = 0x53, // Write CTRL display
-  RDCTRLD    = 0x54, // Read CTRL value display
-  WRCACE     = 0x55, // Write content adaptive brightness control and color enhancement
-  RDCABC     = 0x56, // Read content adaptive brightness control
-  WRCABCMB   = 0x5E, // Write CACB minimum brightness
-  RDCABCMB   = 0x5F, // Read CACB minimum brightness
-  RDABCSDR   = 0x68, // Read automatic brightness control self-diagnostic result
-  RAMCTRL    = 0xB0, // RAM control
-  RGBCTRL    = 0xB1, // RGB interface control
-  PORCTRL    = 0xB2, // Porch setting
-  FRCTRL1    = 0xB3, // Frame rate control 1 (in partial mode/idle colors)
-  PARCTRL    = 0xB5, // Partial control
-  GCTRL      = 0xB7, // Gate control
-  GTADJ      = 0xB8, // Gate on timing adjustment
-  DGMEN      = 0xBA, // Digital gamma enable
-  VCOMS      = 0xBB, // VCOM setting
-  POWSAVE    = 0xBC, // Power saving mode
-  DLPOFFSAVE = 0xBD, // Display off power save
-  LCMCTRL    = 0xC0, // LCM control
-  IDSET      = 0xC1, // ID code setting
-  VDVVRHEN   = 0xC2, // VDV and VRH command enable
-  VRHS       = 0xC3, // VRH set
-  VDVS       = 0xC4, // VDV set
-  VCMOFSET   = 0xC5, // VCOM offset set
-  FRCTRL2    = 0xC6, // Frame rate control in normal mode
-  CABCCTRL   = 0xC7, // CABC control
-  REGSEL1    = 0xC8, // Register value selection 1
-  REGSEL2    = 0xCA, // Register value selection 2
-  PWMFRSEL   = 0xCC, // PWM frequency selection
-  PWCTRL1    = 0xD0, // Power control 1
-  VAPVANEN   = 0xD2, // Enable VAP/VAN signal output
-  RDID1      = 0xDA, // Read ID1
-  RDID2      = 0xDB, // Read ID2
-  RDID3      = 0xDC, // Read ID3
-  CMD2EN     = 0xDF, // Command 2 enable
-  PVGAMCTRL  = 0xE0, // Positive voltage gamma control
-  NVGAMCTRL  = 0xE1, // Negative voltage gamma control
-  DGMLUTR    = 0xE2, // Digital gamma look-up table for red
-  DGMLUTB    = 0xE3, // Digital gamma look-up table for blue
-  GATECTRL   = 0xE4, // Gate control
-  SPI2EN     = 0xE7, // SPI2 enable
-  PWCTRL2    = 0xE8, // Power control 2
-  EQCTRL     = 0xE9, // Equalize time control
-  PROMCTRL   = 0xEC, // Program mode control
-  PROMEN     = 0xFA, // Program mode enable
-  NVMSET     = 0xFC, // NVM setting
-  PROMACT    = 0xFE  // Program action
-}
+// Colors fill_with computes before packing and sending them with send_row;
+// even, so BPP12 pairs never straddle two sends
+const DISPLAY_ROW_CHUNK: usize = 64;
 
-#[repr(u8)]
-#[allow(dead_code)]
-pub enum DisplayColorMode {
-  BPP12   = 0b00000011, // 12 bits/pixel
-  BPP16   = 0b00000101, // 16 bits/pixel -> 0bRRRRRGGG_GGGBBBBB
-  BPP18   = 0b00000110, // 18 bits/pixel -> 0bRRRRRR00_GGGGGG00_BBBBBB00
-  BPP16M  = 0b00000111, // 16M truncated -> 0bRRRRR000_GGGGGG00_BBBBB000
-  RGB65K  = 0b01010000, // 65K of RGB interface
-  RGB262K = 0b01100000  // 262K of RGB interface
-}
+// Longest wait for each TE edge, about two refresh periods at 60Hz; a TE pin
+// the module never drives times out and the frame goes out without sync
+const DISPLAY_TE_TIMEOUT_US: u32 = 33_400;
 
-impl BitOr for DisplayColorMode {
-  type Output = u8;
+// Core cycles between TE pin polls while waiting for an edge
+const DISPLAY_TE_POLL_CYCLES: u32 = 100;
 
-  fn bitor(self, rhs: Self) -> Self::Output {
-    self as u8 | rhs as u8
-  }
-}
+// Nesting depth of push_clip
+pub const DISPLAY_CLIP_DEPTH: usize = 8;
+
+// Lines of the controller frame memory, the vertical scroll areas add up to it
+pub const DISPLAY_RAM_LINES: u16 = 320;
+
+// Window setup bytes (CASET, RASET and RAMWR with their parameters); unchanged
+// pixels cheaper to resend than that don't split a draw_image_delta run
+const DISPLAY_WINDOW_OVERHEAD: usize = 11;
+
+// Clocks tried by probe_max_baudrate, slowest first; the RP2040 SPI tops out at
+// half the peripheral clock (62.5MHz at the usual 125MHz)
+const DISPLAY_PROBE_BAUDRATES: [u32; 7] = [10_000_000, 15_000_000, 20_000_000, 25_000_000, 31_250_000, 41_666_666, 62_500_000];
 
-#[repr(u8)]
-#[derive(PartialEq)]
+// Clock for frame memory reads, well under the panel read cycle
+const DISPLAY_READ_BAUDRATE: u32 = 4_000_000;
+
+// Pixels read_region reads per RAMRD
+const DISPLAY_READ_CHUNK: usize = 32;
+
+// BPP16 pixels written by probe_max_baudrate, mixing toggling and flat bits
+const DISPLAY_PROBE_PATTERN: [u8; 16] = [0xA5, 0x5A, 0x0F, 0xF0, 0xFF, 0x00, 0x33, 0xCC, 0x00, 0xFF, 0xC3, 0x3C, 0x55, 0xAA, 0xF0, 0x0F];
+
+// RAMCTRL reset value: MCU interface, 65K/262K colors expanded from the LSB
 #[allow(dead_code)]
-pub enum DisplayColorModeBPP {
-  BPP12,
-  BPP16,  // 16 bits/pixel -> 0bRRRRRGGG_GGGBBBBB
-  BPP18,  // 18 bits/pixel -> 0bRRRRRR00_GGGGGG00_BBBBBB00
-  BPP16M, // 16M truncated -> 0bRRRRR000_GGGGGG00_BBBBB000
-  UNKNOWN 
-}
+pub const DISPLAY_RAM_CONTROL_DEFAULT: [u8; 2] = [0x00, 0xF0];
 
-#[repr(u8)]
+#[derive(Clone, Copy, PartialEq)]
 #[allow(dead_code)]
-pub enum DisplayMADCTL {
-  MH  = 0b00000100, // Display data latch order
-  RGB = 0b00001000, // RGB/BGR order
-  ML  = 0b00010000, // Line address order
-  MV  = 0b00100000, // Page/column order
-  MX  = 0b01000000, // Column address order
-  MY  = 0b10000000  // Page address order
+pub enum DisplayGradientDirection {
+  Horizontal, // from the left edge to the right edge
+  Vertical    // from the top edge to the bottom edge
 }
 
-impl BitOr for DisplayMADCTL {
-  type Output = u8;
+#[derive(Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub enum DisplayTransitionDirection {
+  FromTop,    // the new image pushes the old one down
+  FromBottom  // the new image pushes the old one up
+}
 
-  fn bitor(self, rhs: Self) -> Self::Output {
-    self as u8 | rhs as u8
-  }
+// The tearing effect (TE) output is not broken out on every module; when it
+// is, wire it to GPIO22 so `present` can wait for the vertical blanking.
+// Reads need the panel data output (SDO, or SDA in 3-wire mode) wired to
+// GPIO28 (SPI1 RX); the Pico LCD modules don't break it out.
+pub struct DisplayPinsData {
+  pub backlight     : Pin<Gpio13, <Gpio13 as PinId>::Reset>,
+  pub chip_select   : Pin<Gpio9 , <Gpio9  as PinId>::Reset>,
+  pub data_command  : Pin<Gpio8 , <Gpio8  as PinId>::Reset>,
+  pub reset         : Pin<Gpio12, <Gpio12 as PinId>::Reset>,
+  pub spi_clock     : Pin<Gpio10, <Gpio10 as PinId>::Reset>,
+  pub spi_miso      : Option<Pin<Gpio28, <Gpio28 as PinId>::Reset>>,
+  pub spi_mosi      : Pin<Gpio11, <Gpio11 as PinId>::Reset>,
+  pub tearing_effect: Option<Pin<Gpio22, <Gpio22 as PinId>::Reset>>
 }
 
-#[repr(u8)]
+// The same pins already in their final modes, for Display::from_pins when
+// they are configured elsewhere (e.g. by a board support crate)
 #[allow(dead_code)]
-pub enum DisplayRotation {
-  Portrait          = 0b00000000,
-  Landscape         = 0b01100000, // DisplayMADCTL::MV | DisplayMADCTL::MX
-  InvertedLandscape = 0b10100000, // DisplayMADCTL::MV | DisplayMADCTL::MY
-  InvertedPortrait  = 0b11000000  // DisplayMADCTL::MX | DisplayMADCTL::MY
+pub struct DisplayPins {
+  pub backlight     : Pin<Gpio13, PushPullOutput>,
+  pub chip_select   : Pin<Gpio9 , PushPullOutput>,
+  pub data_command  : Pin<Gpio8 , PushPullOutput>,
+  pub reset         : Pin<Gpio12, PushPullOutput>,
+  pub spi_clock     : Pin<Gpio10, FunctionSpi>,
+  pub spi_miso      : Option<Pin<Gpio28, FunctionSpi>>,
+  pub spi_mosi      : Pin<Gpio11, FunctionSpi>,
+  pub tearing_effect: Option<Pin<Gpio22, PullDownInput>> // pulled down, so an undriven TE reads low
 }
 
-pub struct DisplayPinsData {
-  pub backlight   : Pin<Gpio13, <Gpio13 as PinId>::Reset>,
-  pub chip_select : Pin<Gpio9 , <Gpio9  as PinId>::Reset>,
-  pub data_command: Pin<Gpio8 , <Gpio8  as PinId>::Reset>,
-  pub reset       : Pin<Gpio12, <Gpio12 as PinId>::Reset>,
-  pub spi_clock   : Pin<Gpio10, <Gpio10 as PinId>::Reset>,
-  pub spi_mosi    : Pin<Gpio11, <Gpio11 as PinId>::Reset>
+// The pins Display drives itself, CS and DC go to the PanelBus with the SPI
+struct DisplayControlPins {
+  _spi_clock    : Pin<Gpio10, FunctionSpi>,
+  _spi_mosi     : Pin<Gpio11, FunctionSpi>,
+  backlight     : Pin<Gpio13, PushPullOutput>,
+  reset         : Pin<Gpio12, PushPullOutput>,
+  spi_miso      : Option<Pin<Gpio28, FunctionSpi>>,
+  tearing_effect: Option<Pin<Gpio22, PullDownInput>>
 }
 
-struct DisplayPins {
-  _spi_clock  : Pin<Gpio10, FunctionSpi>,
-  _spi_mosi   : Pin<Gpio11, FunctionSpi>,
-  backlight   : Pin<Gpio13, PushPullOutput>,
+struct DisplayInterface {
   chip_select : Pin<Gpio9 , PushPullOutput>,
   data_command: Pin<Gpio8 , PushPullOutput>,
-  reset       : Pin<Gpio12, PushPullOutput>
+  spi         : Spi<Enabled, SPI1, 8>
+}
+
+impl PanelInterface for DisplayInterface {
+  fn set_chip_select(&mut self, high: bool) {
+    if high {
+      self.chip_select.set_high().unwrap();
+    }
+    else {
+      self.chip_select.set_low().unwrap();
+    }
+  }
+
+  fn set_data_command(&mut self, high: bool) {
+    if high {
+      self.data_command.set_high().unwrap();
+    }
+    else {
+      self.data_command.set_low().unwrap();
+    }
+  }
+
+  fn write(&mut self, bytes: &[u8]) {
+    self.spi.write(bytes).unwrap();
+  }
+
+  // Without spi_miso the bytes read are whatever the floating RX line gives
+  fn transfer(&mut self, bytes: &mut [u8]) {
+    self.spi.transfer(bytes).unwrap();
+  }
+
+  fn wait_idle(&mut self) {
+    while self.spi.is_busy() {}
+  }
+
+  fn delay_cycles(&mut self, cycles: u32) {
+    cortex_m::asm::delay(cycles);
+  }
 }
 
-struct DisplayTextData {
-  background_color: Option<u32>,
-  foreground_color: u32,
-  pixel_height    : u16,
-  pixel_width     : u16
+// Optional settings of Display::new; DisplayConfig::default() keeps the plain
+// init that clears the screen to black
+//
+// The CS and DC delays give the lines time to settle after toggling them and
+// before the clock starts (and after it stops, before CS is released). Leave
+// them at 0 unless long wires or level shifters between the Pico and the panel
+// cause intermittent garbage; a few microseconds is usually plenty.
+pub struct DisplayConfig<'a> {
+  pub backlight_active_low : bool,             // the backlight transistor turns on with the pin low
+  pub clear_on_init        : bool,             // without a splash the RAM is cleared before the panel turns on
+  pub cs_delay_us          : u32,
+  pub dc_delay_us          : u32,
+  pub init_table           : &'static [DisplayInitStep],
+  pub ram_control          : Option<[u8; 2]>,   // RAMCTRL parameters for clones that latch RAM writes differently
+  pub splash               : Option<&'a [u8]>, // full screen image in the init color mode, shown as soon as the panel is awake
+  pub text_background_color: Option<u32>,      // initial values of the text setters
+  pub text_foreground_color: u32,
+  pub text_scale           : u16
+}
+
+impl Default for DisplayConfig<'_> {
+  fn default() -> Self {
+    Self {
+      backlight_active_low : false,
+      clear_on_init        : true,
+      cs_delay_us          : 0,
+      dc_delay_us          : 0,
+      init_table           : DISPLAY_INIT_TABLE,
+      ram_control          : None,
+      splash               : None,
+      text_background_color: None,
+      text_foreground_color: 0xFFFFFFFF,
+      text_scale           : 1
+    }
+  }
 }
 
+// Receives the pixel data streams as they go out to the panel (send_data
+// without a framebuffer, the whole frame on present with one), e.g. to
+// forward them over RTT or a second SPI to record what was drawn. Only the
+// pixel bytes are passed: no commands and no window coordinates
+#[cfg(feature = "tee")]
+pub type DisplayTee = fn(&[u8]);
+
 pub struct DisplaySpiData<'a> {
   pub baudrate  : HertzU32,
   pub clock     : HertzU32,
@@ -218,348 +320,2030 @@ pub struct DisplaySpiData<'a> {
   pub resets    : &'a mut RESETS
 }
 
-pub struct Display {
-  bpp   : DisplayColorModeBPP,
-  height: u16,
-  pins  : DisplayPins,
-  spi   : Spi<Enabled, SPI1, 8>,
-  text  : DisplayTextData,
-  width : u16
+pub struct Display<const BUFFER_SIZE: usize = DISPLAY_BUFFER_SIZE> {
+  backlight_active_low: bool,
+  baudrate            : HertzU32,
+  blanked             : bool,
+  bpp                 : DisplayColorModeBPP,
+  brightness          : u8,
+  bus                 : PanelBus<DisplayInterface>,
+  clip                : Vec<Rect, DISPLAY_CLIP_DEPTH>,
+  display_on          : bool,
+  fill_pattern        : Option<([u8; 8], Option<u32>)>, // stipple rows and the color of its clear bits
+  framebuffer         : Option<ScreenBuffer<'static>>,
+  grayscale           : bool,
+  height              : u16,
+  init_table          : &'static [DisplayInitStep],
+  madctl              : Madctl,
+  nibble_order        : DisplayNibbleOrder,
+  peripheral_clock    : HertzU32,
+  pins                : DisplayControlPins,
+  ram_control         : Option<[u8; 2]>,
+  reset_held          : bool,
+  rotation            : DisplayRotation,
+  sleeping            : bool,
+  tearing_effect      : bool,
+  #[cfg(feature = "tee")]
+  tee                 : Option<DisplayTee>,
+  text                : TextStyle,
+  width               : u16,
+  window_first_color  : Option<u32>, // first color send_row sent to the window last opened
+  window_pixels       : WindowCounter, // left to write in the window last opened
+  #[cfg(debug_assertions)]
+  window_bpp          : usize // bytes per pixel when the window was opened
 }
 
 #[allow(dead_code)]
-impl Display {
-  pub fn new(width: u16, height: u16, bpp: DisplayColorModeBPP, rotation: DisplayRotation, pins_data: DisplayPinsData, spi_data: DisplaySpiData, delay: &mut Delay) -> Self {
-    let mut display = Self {
-      bpp: DisplayColorModeBPP::UNKNOWN,
-      height: height,
-      pins  : DisplayPins {
-        _spi_clock  : pins_data.spi_clock.into_mode::<FunctionSpi>(),
-        _spi_mosi   : pins_data.spi_mosi.into_mode::<FunctionSpi>(),
-        backlight   : pins_data.backlight.into_push_pull_output(),
-        chip_select : pins_data.chip_select.into_push_pull_output(),
-        data_command: pins_data.data_command.into_push_pull_output(),
-        reset       : pins_data.reset.into_push_pull_output()
-      },
-      spi   : Spi::new(spi_data.peripheral).init(
+impl<const BUFFER_SIZE: usize> Display<BUFFER_SIZE> {
+  pub fn new(size: Size, bpp: DisplayColorModeBPP, rotation: DisplayRotation, pins_data: DisplayPinsData, spi_data: DisplaySpiData, config: DisplayConfig, delay: &mut impl DelayMs<u32>) -> Self {
+    let pins = DisplayPins {
+      backlight     : pins_data.backlight.into_push_pull_output(),
+      chip_select   : pins_data.chip_select.into_push_pull_output(),
+      data_command  : pins_data.data_command.into_push_pull_output(),
+      reset         : pins_data.reset.into_push_pull_output(),
+      spi_clock     : pins_data.spi_clock.into_mode::<FunctionSpi>(),
+      spi_miso      : pins_data.spi_miso.map(|pin| pin.into_mode::<FunctionSpi>()),
+      spi_mosi      : pins_data.spi_mosi.into_mode::<FunctionSpi>(),
+      tearing_effect: pins_data.tearing_effect.map(|pin| pin.into_pull_down_input())
+    };
+
+    Self::from_pins(size, bpp, rotation, pins, spi_data, config, delay)
+  }
+
+  // Same as new with pins already in their modes, which are used as they are
+  pub fn from_pins(size: Size, bpp: DisplayColorModeBPP, rotation: DisplayRotation, pins: DisplayPins, spi_data: DisplaySpiData, config: DisplayConfig, delay: &mut impl DelayMs<u32>) -> Self {
+    let DisplayPins {
+      backlight,
+      chip_select,
+      data_command,
+      reset,
+      spi_clock,
+      spi_miso,
+      spi_mosi,
+      tearing_effect
+    } = pins;
+
+    let interface = DisplayInterface {
+      chip_select,
+      data_command,
+      spi: Spi::new(spi_data.peripheral).init(
         spi_data.resets,
         spi_data.clock,
         spi_data.baudrate,
         spi_data.mode
+      )
+    };
+
+    let mut display = Self {
+      backlight_active_low: config.backlight_active_low,
+      baudrate            : spi_data.baudrate,
+      blanked             : false,
+      bpp                 : DisplayColorModeBPP::UNKNOWN,
+      brightness          : 0xFF,
+      bus                 : PanelBus::new(
+        interface,
+        microseconds_to_cycles(config.cs_delay_us, spi_data.clock),
+        microseconds_to_cycles(config.dc_delay_us, spi_data.clock)
       ),
-      text  : DisplayTextData {
-        background_color: None,
-        foreground_color: 0xFFFFFFFF,
-        pixel_height    : 1,
-        pixel_width     : 1
+      clip                : Vec::new(),
+      display_on          : false,
+      fill_pattern        : None,
+      framebuffer         : None,
+      grayscale           : false,
+      height              : size.height,
+      init_table          : config.init_table,
+      madctl              : Madctl::new(),
+      nibble_order        : DisplayNibbleOrder::MsbFirst,
+      peripheral_clock    : spi_data.clock,
+      pins                : DisplayControlPins {
+        _spi_clock: spi_clock,
+        _spi_mosi : spi_mosi,
+        backlight,
+        reset,
+        spi_miso,
+        tearing_effect
       },
-      width : width
+      ram_control         : config.ram_control,
+      reset_held          : false,
+      rotation,
+      sleeping            : true,
+      tearing_effect      : false,
+      #[cfg(feature = "tee")]
+      tee                 : None,
+      text                : TextStyle {
+        background_color: config.text_background_color,
+        direction       : DisplayTextDirection::LeftToRight,
+        foreground_color: config.text_foreground_color,
+        outline         : None,
+        scale           : TextScale::uniform(config.text_scale)
+      },
+      width               : size.width,
+      window_first_color  : None,
+      window_pixels       : WindowCounter::default(),
+      #[cfg(debug_assertions)]
+      window_bpp          : 0
     };
 
-    display.hard_reset(delay);
-    display.soft_reset(delay);
-    display.set_sleep_mode(false);
-    display.set_bpp(bpp);
-    display.set_rotation(rotation);
-    display.set_inversion_mode(true);
-    display.set_normal_mode();
-    display.fill(0);
+    display.set_backlight(false);
+
+    display.init(bpp, rotation, delay);
+
+    // Out of sleep and with the color mode set the splash can be drawn and the
+    // panel turned on right away, while the caller goes on with its own init
+    match config.splash {
+      Some(splash) => {
+        let length = display.width as usize * display.height as usize * display.bytes_per_pixel();
+
+        if splash.len() < length {
+          display.fill(0);
+        }
+
+        display.open_window(0, 0, display.width - 1, display.height - 1);
+        display.send_data(&splash[..splash.len().min(length)]);
+      },
+      None => {
+        // Skipping it saves a full screen write at boot, but the panel turns on
+        // showing whatever the RAM holds (noise after power up) until the
+        // caller draws over it, so only do it when a full frame follows at once
+        if config.clear_on_init {
+          display.fill(0);
+        }
+      }
+    }
+
     display.set_backlight(true);
     display.set_display(true);
 
     display
   }
 
-  pub fn draw_solid_rect(&mut self, mut x: u16, mut y: u16, mut width: u16, mut height: u16, color: u32) {
-    if self.bpp == DisplayColorModeBPP::UNKNOWN {
-      return;
+  fn init(&mut self, bpp: DisplayColorModeBPP, rotation: DisplayRotation, delay: &mut impl DelayMs<u32>) {
+    self.hard_reset(delay);
+
+    let init_table = self.init_table;
+
+    for &(command, params, delay_ms) in init_table {
+      match (command, params.is_empty()) {
+        (command, true) if command == DisplayCommand::COLMOD as u8 => self.set_bpp(bpp),
+        (command, true) if command == DisplayCommand::MADCTL as u8 => {
+          self.set_rotation(rotation);
+        },
+        _ => {
+          self.send_raw_command(command);
+
+          if !params.is_empty() {
+            self.bus.data(params);
+          }
+        }
+      }
+
+      if delay_ms > 0 {
+        delay.delay_ms(delay_ms);
+      }
+    }
+
+    self.display_on = false;
+    self.sleeping = false;
+
+    self.set_tearing_effect(self.pins.tearing_effect.is_some());
+
+    if let Some(params) = self.ram_control {
+      self.set_ram_control(params);
     }
+  }
 
-    if x >= self.width {
-      x = self.width - 1;
+  // Resets the panel and runs the init sequence again with the current color
+  // mode, rotation and brightness, e.g. to recover a panel left in a bad state
+  // by a glitch. The screen is cleared, or redrawn from the framebuffer if set
+  pub fn reinit(&mut self, delay: &mut impl DelayMs<u32>) {
+    self.bus.abort_batch();
+
+    self.init(self.bpp, self.rotation, delay);
+
+    if self.framebuffer.is_none() || self.present().is_err() {
+      self.fill(0);
     }
 
-    if y >= self.height {
-      y = self.height - 1;
+    if self.brightness != 0xFF {
+      self.set_brightness(self.brightness);
     }
 
-    if x + width >= self.width {
-      width = self.width - x;
+    self.set_backlight(true);
+    self.set_display(true);
+
+    self.blanked = false;
+  }
+
+  // Shutdown counterpart of new: backlight off, DISPOFF and sleep in, and
+  // with hold_reset the reset line is left low too. The panel then draws
+  // about the sleep mode current of the datasheet (microamps) plus whatever
+  // the module backlight circuit leaks; holding reset keeps it from reacting
+  // to noise on the bus but loses the RAM and every register
+  pub fn power_off(&mut self, delay: &mut impl DelayMs<u32>, hold_reset: bool) {
+    self.bus.abort_batch();
+
+    self.set_backlight(false);
+    self.set_display(false);
+    self.set_sleep_mode(true);
+
+    // SLPIN needs 5ms before anything else is sent
+    delay.delay_ms(5);
+
+    if hold_reset {
+      self.pins.reset.set_low().unwrap();
+
+      self.reset_held = true;
     }
+  }
 
-    if y + height >= self.height {
-      height = self.height - y;
+  // Resumes after power_off: out of sleep with the picture that was in RAM,
+  // or a full reinit when the reset was held
+  pub fn wake(&mut self, delay: &mut impl DelayMs<u32>) {
+    if self.reset_held {
+      self.reinit(delay);
+
+      return;
     }
 
-    self.set_window(x, y, x + width - 1, y + height - 1);
+    self.set_sleep_mode(false);
 
-    let mut bytes_per_pixel = 0u8;
-    let buf = &mut [0, 0, 0];
-    let pixels_count = width * height;
+    // SLPOUT needs 120ms before the panel is fully up
+    delay.delay_ms(120);
 
-    if self.bpp == DisplayColorModeBPP::BPP12 {
-      // Nothing yet
+    self.set_display(true);
+    self.set_backlight(true);
+
+    self.blanked = false;
+  }
+
+  // Empty rects (width or height 0, e.g. from the resize demo) draw nothing;
+  // they must never reach the window math below, where x + width - 1 would wrap
+  pub fn draw_solid_rect(&mut self, x: u16, y: u16, width: u16, height: u16, color: u32) {
+    if self.bpp == DisplayColorModeBPP::UNKNOWN {
+      return;
     }
-    else if self.bpp == DisplayColorModeBPP::BPP16 {
-      bytes_per_pixel = 2;
 
-      let color_hi = ((color >> 8) & 0xFF) as u8;
-      let color_lo = ((color >> 0) & 0xFF) as u8;
+    let (start, end) = match Rect::new(x, y, width, height).clipped_window(&self.clip()) {
+      Some(window) => window,
+      None         => return
+    };
+
+    let clipped = Rect::from_corners(start, end);
 
-      buf[0] = color_hi;
-      buf[1] = color_lo;
+    if let Some((pattern, background_color)) = self.fill_pattern {
+      self.draw_pattern_rect(clipped, color, &pattern, background_color);
+
+      return;
     }
-    else if self.bpp == DisplayColorModeBPP::BPP18 || self.bpp == DisplayColorModeBPP::BPP16M {
-      bytes_per_pixel = 3;
 
-      let color_r = ((color >> 16) & 0xFF) as u8;
-      let color_g = ((color >> 8 ) & 0xFF) as u8;
-      let color_b = ((color >> 0 ) & 0xFF) as u8;
+    self.open_window(start.x, start.y, end.x, end.y);
 
-      buf[0] = color_r;
-      buf[1] = color_g;
-      buf[2] = color_b;
+    let buf = &mut [0u8; BUFFER_SIZE];
+    let mut pixels_count = clipped.width as usize * clipped.height as usize;
+    let (pixel, bytes_per_pixel) = self.pack_color(color);
+
+    // In BPP12 a "pixel" here is a pair of them. With an odd count the last
+    // pair wraps around the window and repaints its first pixel the same color
+    if self.bpp == DisplayColorModeBPP::BPP12 {
+      pixels_count = pixels_count.div_ceil(2);
     }
 
-    for _ in 0..pixels_count {
-      self.send_data(&buf[0..bytes_per_pixel as usize]);
+    if bytes_per_pixel == 0 || BUFFER_SIZE < bytes_per_pixel {
+      return;
     }
-    // let chunks = pixels_count / BUFFER_SIZE;
-    // let rest = pixels_count % BUFFER_SIZE;
 
-    // let buf = &mut [0u8; BUFFER_SIZE as usize * 2];
+    let chunk_pixels = BUFFER_SIZE / bytes_per_pixel;
+
+    for i in 0..chunk_pixels {
+      buf[i * bytes_per_pixel..(i + 1) * bytes_per_pixel].copy_from_slice(&pixel[0..bytes_per_pixel]);
+    }
 
-    // for i in 0..BUFFER_SIZE {
-    //   buf[i as usize * 2    ] = color_hi;
-    //   buf[i as usize * 2 + 1] = color_lo;
-    // }
+    let chunks = pixels_count / chunk_pixels;
+    let rest = pixels_count % chunk_pixels;
 
-    // for _ in 0..chunks {
-    //   self.send_data(buf);
-    // }
+    for _ in 0..chunks {
+      self.send_data(&buf[0..chunk_pixels * bytes_per_pixel]);
+    }
 
-    // if rest > 0 {
-    //   self.send_data(&buf[0..rest as usize * 2]);
-    // }
+    if rest > 0 {
+      self.send_data(&buf[0..rest * bytes_per_pixel]);
+    }
   }
 
-  pub fn fill(&mut self, color: u32) {
-    self.draw_solid_rect(0, 0, self.width, self.height, color);
+  pub fn fill_rect(&mut self, rect: Rect, color: u32) {
+    self.draw_solid_rect(rect.x, rect.y, rect.width, rect.height, color);
   }
 
-  pub fn hard_reset(&mut self, delay: &mut Delay) {
-    self.pins.chip_select.set_low().unwrap();
+  // While set, draw_solid_rect and everything built on it (fill, text, widgets)
+  // paint the color only where the 8x8 pattern bit is set (bit 7 of each row
+  // byte is the leftmost column) and background_color elsewhere, or leave those
+  // pixels alone with None. The pattern is tied to the screen grid, so
+  // neighbouring fills line up. Skipping is much slower, a window per run
+  pub fn set_fill_pattern(&mut self, pattern: Option<&[u8; 8]>, background_color: Option<u32>) {
+    self.fill_pattern = pattern.map(|pattern| (*pattern, background_color));
+  }
 
-    self.pins.reset.set_high().unwrap();
+  fn draw_pattern_rect(&mut self, rect: Rect, color: u32, pattern: &[u8; 8], background_color: Option<u32>) {
+    if let Some(background_color) = background_color {
+      self.fill_with(rect.x, rect.y, rect.width, rect.height, |pixel_x, pixel_y| {
+        if pattern_bit(pattern, pixel_x, pixel_y) {
+          color
+        }
+        else {
+          background_color
+        }
+      });
 
-    delay.delay_ms(50);
+      return;
+    }
 
-    self.pins.reset.set_low().unwrap();
+    // The runs are drawn with the pattern off, then it is put back
+    let fill_pattern = self.fill_pattern.take();
 
-    delay.delay_ms(50);
+    for run in pattern_runs(pattern, rect) {
+      self.draw_solid_rect(run.x, run.y, run.width, run.height, color);
+    }
 
-    self.pins.reset.set_high().unwrap();
+    self.fill_pattern = fill_pattern;
+  }
 
-    delay.delay_ms(150);
+  // Streams a region whose pixel colors come from f(x, y), called in row order
+  pub fn fill_with<F: FnMut(u16, u16) -> u32>(&mut self, x: u16, y: u16, width: u16, height: u16, f: F) {
+    if self.bpp == DisplayColorModeBPP::UNKNOWN {
+      return;
+    }
+
+    let (x, y, width, height) = match self.clip_region(x, y, width, height) {
+      Some(region) => region,
+      None         => return
+    };
+
+    self.open_window(x, y, x + width - 1, y + height - 1);
 
-    self.pins.chip_select.set_high().unwrap();
+    scan_region(x, y, width, height, &mut [0u32; DISPLAY_ROW_CHUNK], f, |colors| self.send_row(colors));
   }
 
-  pub fn send_command(&mut self, command: DisplayCommand) {
-    self.pins.chip_select.set_low().unwrap();
+  // Packs the colors in the active color mode and sends them, a buffer of
+  // BUFFER_SIZE bytes at a time (a single write when they fit). In BPP12 the
+  // colors go in pairs; with an odd count the last one is paired with the
+  // first color sent to the window, whose pixel the extra one wraps around to
+  // and repaints unchanged. That only holds for the call that fills the
+  // window, so a window streamed in several calls splits it at even counts
+  pub fn send_row(&mut self, colors: &[u32]) {
+    let (colors_per_pack, bytes_per_pack) = match self.bpp {
+      DisplayColorModeBPP::BPP12 => (2, 3),
+      _                          => (1, self.bytes_per_pixel())
+    };
 
-    self.pins.data_command.set_low().unwrap();
+    if colors.is_empty() || bytes_per_pack == 0 || BUFFER_SIZE < bytes_per_pack {
+      return;
+    }
 
-    self.spi.write(&[command as u8]).unwrap();
+    let first = *self.window_first_color.get_or_insert(colors[0]);
+    let wrap = self.apply_grayscale(first);
+    let buf = &mut [0u8; BUFFER_SIZE];
 
-    self.pins.chip_select.set_high().unwrap();
+    for part in colors.chunks(BUFFER_SIZE / bytes_per_pack * colors_per_pack) {
+      let colors = part.iter().map(|&color| self.apply_grayscale(color));
+      let length = pack_row(self.bpp, self.nibble_order, colors, wrap, buf);
+
+      self.send_data(&buf[0..length]);
+    }
   }
 
-  pub fn send_data(&mut self, data: &[u8]) {
-    self.pins.chip_select.set_low().unwrap();
+  // Draws the rects in order with CS held low for the whole batch, which is
+  // left open if the caller had already begun one. Consecutive rects of the
+  // same color that together form a rectangle (e.g. the cells of a bar) are
+  // merged and sent through a single window
+  pub fn fill_rects(&mut self, rects: &[(Rect, u32)]) {
+    let batching = self.bus.is_batching();
+    let mut pending: Option<(Rect, u32)> = None;
 
-    self.pins.data_command.set_high().unwrap();
+    self.bus.begin_batch();
 
-    self.spi.write(data).unwrap();
+    for &(rect, color) in rects {
+      if rect.is_empty() {
+        continue;
+      }
 
-    self.pins.chip_select.set_high().unwrap();
-  }
+      pending = match pending {
+        Some((pending_rect, pending_color)) => {
+          match pending_rect.merge_adjacent(&rect) {
+            Some(merged) if pending_color == color => Some((merged, color)),
+            _ => {
+              self.draw_solid_rect(pending_rect.x, pending_rect.y, pending_rect.width, pending_rect.height, pending_color);
 
-  pub fn set_backlight(&mut self, on: bool) {
-    if on {
-      self.pins.backlight.set_high().unwrap();
+              Some((rect, color))
+            }
+          }
+        },
+        None => Some((rect, color))
+      };
     }
-    else {
-      self.pins.backlight.set_low().unwrap();
+
+    if let Some((rect, color)) = pending {
+      self.draw_solid_rect(rect.x, rect.y, rect.width, rect.height, color);
+    }
+
+    if !batching {
+      self.bus.end_batch();
     }
   }
 
-  pub fn set_bpp(&mut self, bpp: DisplayColorModeBPP) {
-    self.bpp = bpp;
+  // Repeats the tile across the region starting with its top-left corner at
+  // (x, y); the tiles of the last column and row are cut at the region edges
+  pub fn fill_tiled(&mut self, x: u16, y: u16, width: u16, height: u16, tile: &Sprite) {
+    let (tile_x, tile_y) = (x, y);
+
+    let (x, y, width, height) = match self.clip_region(x, y, width, height) {
+      Some(region) => region,
+      None         => return
+    };
 
-    let color_mode: u8;
+    let bytes_per_pixel = self.bytes_per_pixel();
 
-    match self.bpp {
-      DisplayColorModeBPP::BPP12  => color_mode = DisplayColorMode::RGB65K  | DisplayColorMode::BPP12,
-      DisplayColorModeBPP::BPP16  => color_mode = DisplayColorMode::RGB65K  | DisplayColorMode::BPP16,
-      DisplayColorModeBPP::BPP16M => color_mode = DisplayColorMode::RGB65K  | DisplayColorMode::BPP16M,
-      DisplayColorModeBPP::BPP18  => color_mode = DisplayColorMode::RGB262K | DisplayColorMode::BPP12,
-      _                           => color_mode = DisplayColorMode::RGB65K  | DisplayColorMode::BPP16
+    if bytes_per_pixel == 0 || BUFFER_SIZE < bytes_per_pixel || tile.row_bytes(bytes_per_pixel).is_none() {
+      return;
     }
 
-    self.set_color_mode(color_mode);
+    self.open_window(x, y, x + width - 1, y + height - 1);
+
+    // Clipping may cut the region on the left or top, so the tiles keep their
+    // phase from the unclipped origin
+    tile.stream_tiled(Rect::new(x - tile_x, y - tile_y, width, height), bytes_per_pixel, &mut [0u8; BUFFER_SIZE], |bytes| self.send_data(bytes));
   }
 
-  fn set_color_mode(&mut self, mode: u8) {
-    self.send_command(DisplayCommand::COLMOD);
+  // Fills the circle sector going clockwise from start_degrees to end_degrees,
+  // 0 degrees pointing right; see trig::sector_contains for the angle rules
+  pub fn fill_pie(&mut self, center_x: u16, center_y: u16, radius: u16, start_degrees: i32, end_degrees: i32, color: u32) {
+    if self.width == 0 || self.height == 0 {
+      return;
+    }
 
-    self.send_data(&[mode]);
-  }
+    let first_x = center_x.saturating_sub(radius);
+    let last_x = center_x.saturating_add(radius).min(self.width - 1);
+    let first_y = center_y.saturating_sub(radius);
+    let last_y = center_y.saturating_add(radius).min(self.height - 1);
 
-  pub fn set_columns(&mut self, start: u16, end: u16) {
-    if start > end || end >= self.width {
+    if first_x > last_x || first_y > last_y {
       return;
     }
 
-    let start_hi = (start >> 8) as u8;
-    let start_lo = (start & 0xFF) as u8;
+    for y in first_y..=last_y {
+      let dy = y as i32 - center_y as i32;
+      let mut run_start: Option<u16> = None;
 
-    let end_hi = (end >> 8) as u8;
-    let end_lo = (end & 0xFF) as u8;
+      for x in first_x..=last_x + 1 {
+        let inside = x <= last_x && sector_contains(x as i32 - center_x as i32, dy, radius, start_degrees, end_degrees);
 
-    self.send_command(DisplayCommand::CASET);
+        match (inside, run_start) {
+          (true, None) => run_start = Some(x),
+          (false, Some(start)) => {
+            self.draw_solid_rect(start, y, x - start, 1, color);
 
-    self.send_data(&[start_hi, start_lo, end_hi, end_lo]);
+            run_start = None;
+          },
+          _ => {}
+        }
+      }
+    }
   }
 
-  pub fn set_display(&mut self, on: bool) {
-    if on {
-      self.send_command(DisplayCommand::DISPON);
-    }
-    else {
-      self.send_command(DisplayCommand::DISPOFF);
-    }
+  // from and to are 0xRRGGBB, converted to the active color mode per pixel. With
+  // dither the 4x4 Bayer matrix spreads the rounding error between the two
+  // nearest representable colors, hiding the bands of the 5/6/5 modes
+  pub fn fill_gradient(&mut self, rect: Rect, from: u32, to: u32, direction: DisplayGradientDirection, dither: bool) {
+    let bpp = self.bpp;
+    let (step_r, step_g, step_b) = color_steps(bpp);
+
+    let steps = match direction {
+      DisplayGradientDirection::Horizontal => rect.width,
+      DisplayGradientDirection::Vertical   => rect.height
+    }.saturating_sub(1).max(1) as i32;
+
+    let channel = |shift: u32, position: i32, pixel_x: u16, pixel_y: u16, step: u16| -> u8 {
+      let start = ((from >> shift) & 0xFF) as i32;
+      let end = ((to >> shift) & 0xFF) as i32;
+      let value = (start + (end - start) * position / steps) as u8;
+
+      if dither {
+        dither_channel(value, pixel_x, pixel_y, step)
+      }
+      else {
+        value
+      }
+    };
+
+    self.fill_with(rect.x, rect.y, rect.width, rect.height, |pixel_x, pixel_y| {
+      let position = match direction {
+        DisplayGradientDirection::Horizontal => pixel_x.saturating_sub(rect.x),
+        DisplayGradientDirection::Vertical   => pixel_y.saturating_sub(rect.y)
+      } as i32;
+
+      rgb888_to_color(
+        bpp,
+        channel(16, position, pixel_x, pixel_y, step_r),
+        channel(8 , position, pixel_x, pixel_y, step_g),
+        channel(0 , position, pixel_x, pixel_y, step_b)
+      )
+    });
   }
 
-  pub fn set_inversion_mode(&mut self, on: bool) {
-    if on {
-      self.send_command(DisplayCommand::INVON);
-    }
-    else {
-      self.send_command(DisplayCommand::INVOFF);
-    }
+  // Converts a 0xRRGGBB color to the format expected by the drawing methods in
+  // the active color mode
+  pub fn color_from_rgb888(&self, color: u32) -> u32 {
+    rgb888_to_color(self.bpp, (color >> 16) as u8, (color >> 8) as u8, color as u8)
   }
 
-  pub fn set_normal_mode(&mut self) {
-    self.send_command(DisplayCommand::NORON);
+  // Restricts every drawing to the rect intersected with the current clip, until
+  // the matching pop_clip. An empty intersection clips everything away
+  pub fn push_clip(&mut self, rect: Rect) -> Result<(), DisplayError> {
+    let current = self.clip();
+    let clip = current.intersection(&rect).unwrap_or(Rect::new(current.x, current.y, 0, 0));
+
+    self.clip.push(clip).map_err(|_| DisplayError::ClipStackFull)
   }
 
-  pub fn set_rotation(&mut self, rotation: DisplayRotation) {
-    self.send_command(DisplayCommand::MADCTL);
+  pub fn pop_clip(&mut self) -> Option<Rect> {
+    self.clip.pop()
+  }
 
-    self.send_data(&[rotation as u8]);
+  // Effective clip, the whole screen when nothing was pushed
+  pub fn clip(&self) -> Rect {
+    match self.clip.last() {
+      Some(clip) => *clip,
+      None       => Rect::new(0, 0, self.width, self.height)
+    }
   }
 
-  pub fn set_sleep_mode(&mut self, on: bool) {
-    if on {
-      self.send_command(DisplayCommand::SLPIN);
+  // Part of the region inside the clip, None when nothing is left to draw
+  fn clip_region(&self, x: u16, y: u16, width: u16, height: u16) -> Option<(u16, u16, u16, u16)> {
+    self.clip()
+      .intersection(&Rect::new(x, y, width, height))
+      .map(|rect| (rect.x, rect.y, rect.width, rect.height))
+  }
+
+  // Bytes of one pixel of the color in the active color mode and how many are
+  // used; in BPP12 the bytes of two pixels of the 0xRGB color
+  fn pack_color(&self, color: u32) -> ([u8; 3], usize) {
+    pack_color(self.bpp, self.nibble_order, self.apply_grayscale(color))
+  }
+
+  fn apply_grayscale(&self, color: u32) -> u32 {
+    if self.grayscale {
+      color_to_grayscale(self.bpp, color)
     }
     else {
-      self.send_command(DisplayCommand::SLPOUT);
+      color
     }
   }
 
-  pub fn set_rows(&mut self, start: u16, end: u16) {
-    if start > end || end >= self.height {
-      return;
-    }
-
-    let start_hi = (start >> 8) as u8;
-    let start_lo = (start & 0xFF) as u8;
+  pub fn fill(&mut self, color: u32) {
+    self.draw_solid_rect(0, 0, self.width, self.height, color);
+  }
 
-    let end_hi = (end >> 8) as u8;
-    let end_lo = (end & 0xFF) as u8;
+  // Fills only the rows start_row..=end_row across the whole width, in a single
+  // window: a cheap clear behind something that only moves within those rows
+  pub fn fill_rows(&mut self, start_row: u16, end_row: u16, color: u32) -> Result<(), DisplayError> {
+    let band = row_band(start_row, end_row, self.width, self.height)?;
 
-    self.send_command(DisplayCommand::RASET);
+    self.fill_rect(band, color);
 
-    self.send_data(&[start_hi, start_lo, end_hi, end_lo]);
+    Ok(())
   }
 
-  pub fn set_text_background_color(&mut self, color: Option<u32>) {
-    self.text.background_color = color;
-  }
+  pub fn hard_reset(&mut self, delay: &mut impl DelayMs<u32>) {
+    self.bus.interface().set_chip_select(false);
 
-  pub fn set_text_foreground_color(&mut self, color: u32) {
-    self.text.foreground_color = color;
-  }
+    self.pins.reset.set_high().unwrap();
 
-  pub fn set_text_pixel_height(&mut self, pixel_height: u16) {
-    self.text.pixel_height = pixel_height;
-  }
+    delay.delay_ms(50);
 
-  pub fn set_text_pixel_width(&mut self, pixel_width: u16) {
-    self.text.pixel_width = pixel_width;
-  }
+    self.pins.reset.set_low().unwrap();
+
+    delay.delay_ms(50);
 
-  pub fn set_window(&mut self, start_x: u16, start_y: u16, end_x: u16, end_y: u16) {
-    self.set_columns(start_x, end_x);
+    self.pins.reset.set_high().unwrap();
+
+    self.reset_held = false;
+
+    delay.delay_ms(150);
 
-    self.set_rows(start_y, end_y);
+    self.bus.interface().set_chip_select(true);
 
-    self.send_command(DisplayCommand::RAMWR);
+    self.display_on = false;
+    self.sleeping = true;
   }
 
-  pub fn soft_reset(&mut self, delay: &mut Delay) {
-    self.send_command(DisplayCommand::SWRESET);
+  pub fn send_command(&mut self, command: DisplayCommand) {
+    self.send_raw_command(command as u8);
+  }
 
-    delay.delay_ms(150);
+  // Bypasses DisplayCommand on purpose: the byte goes to the panel as-is, so
+  // vendor-specific or undocumented registers can be sent without extending the enum
+  pub fn send_raw_command(&mut self, command: u8) {
+    self.bus.command(command);
   }
 
-  pub fn draw_text(&mut self, x: u16, y: u16, text: String<31>) {
-    let char_width = 8 * self.text.pixel_width as u16;
-    let char_height = 8 * self.text.pixel_height as u16;
+  pub fn send_data(&mut self, data: &[u8]) {
+    #[cfg(debug_assertions)]
+    self.check_data_length(data.len());
 
-    let mut render_x = x;
-    let mut render_y = y;
+    self.window_pixels.sent(self.bpp, data.len());
 
-    for c in text.chars() {
-      let font_index = c as usize;
+    if self.framebuffer.is_some() {
+      self.write_framebuffer(data);
+    }
+    else {
+      self.write_pixel_data(data);
+    }
+  }
 
-      if font_index == 0x0A {
-        render_x = x;
-        render_y += char_height;
+  // PanelBus::data for pixel data going to the panel, which is also handed to the tee
+  fn write_pixel_data(&mut self, data: &[u8]) {
+    #[cfg(feature = "tee")]
+    if let Some(tee) = self.tee {
+      tee(data);
+    }
 
-        continue;
-      }
+    self.bus.data(data);
+  }
 
-      let char = FONT[font_index];
+  // Only with the tee feature. The callback runs inline on every pixel write,
+  // so anything slower than the SPI itself (RTT with a full buffer, a slower
+  // second SPI) slows every drawing down by that much; None removes it
+  #[cfg(feature = "tee")]
+  pub fn set_tee(&mut self, tee: Option<DisplayTee>) {
+    self.tee = tee;
+  }
 
-      for char_row in char {
-        for char_column in (0..=7).rev() {
-          if char_row & (1 << char_column) != 0 {
-            self.draw_solid_rect(render_x, render_y, self.text.pixel_width, self.text.pixel_height, self.text.foreground_color);
-          }
-          else if self.text.background_color != None {
-            self.draw_solid_rect(render_x, render_y, self.text.pixel_width, self.text.pixel_height, self.text.background_color.unwrap());
-          }
+  // send_data in chunks of chunk bytes (0 sends it whole, keep it a multiple
+  // of the pixel size), calling progress with the bytes sent so far after each,
+  // e.g. to draw a progress bar during a big blit. CS stays asserted from the
+  // first chunk to the last one
+  pub fn send_data_chunked(&mut self, data: &[u8], chunk: usize, progress: impl FnMut(usize)) {
+    let batching = self.bus.is_batching();
 
-          render_x += self.text.pixel_width;
-        }
+    self.bus.begin_batch();
 
-        render_x -= char_width;
-        render_y += self.text.pixel_height;
-      }
+    send_chunked(data, chunk, |part| self.send_data(part), progress);
 
-      render_x += char_width;
-      render_y -= char_height;
+    if !batching {
+      self.bus.end_batch();
     }
   }
 
-  pub fn height(&self) -> u16 {
-    self.height
-  }
+  // Development builds only: data sent to a window in another color mode than
+  // it was opened with, or cut in the middle of a pixel, is almost always a
+  // bpp/data mismatch that garbles the image from there on
+  #[cfg(debug_assertions)]
+  fn check_data_length(&self, length: usize) {
+    let bytes_per_pixel = self.bytes_per_pixel();
 
-  pub fn width(&self) -> u16 {
+    if bytes_per_pixel != self.window_bpp {
+      defmt::warn!("send_data: color mode changed since the window was set ({=usize} -> {=usize} bytes per pixel)", self.window_bpp, bytes_per_pixel);
+    }
+    else if bytes_per_pixel > 0 && !length.is_multiple_of(bytes_per_pixel) {
+      defmt::warn!("send_data: {=usize} bytes is not a whole number of {=usize} byte pixels", length, bytes_per_pixel);
+    }
+  }
+
+  // RDDSDR: bit 7 set when the registers loaded correctly from NVM, bit 6
+  // set when the display functionality self-test passed. Needs spi_miso
+  pub fn read_self_diagnostic(&mut self) -> u8 {
+    let data = &mut [0u8; 1];
+
+    self.bus.read(DisplayCommand::RDDSDR as u8, data);
+
+    data[0]
+  }
+
+  // RDDID: manufacturer, driver version and driver ID (0x85, 0x85, 0x52 on
+  // most ST7789V). Reads longer than 8 bits start after one dummy clock
+  pub fn read_display_id(&mut self) -> [u8; 3] {
+    let raw = &mut [0u8; 4];
+
+    self.bus.read(DisplayCommand::RDDID as u8, raw);
+
+    let mut id = [0u8; 3];
+
+    skip_dummy_clock(raw, &mut id);
+
+    id
+  }
+
+  // RDDST: booster, address orders, color mode, sleep/partial/idle modes,
+  // inversion, display on, tearing effect and gamma curve, see the datasheet
+  pub fn read_display_status(&mut self) -> [u8; 4] {
+    let raw = &mut [0u8; 5];
+
+    self.bus.read(DisplayCommand::RDDST as u8, raw);
+
+    let mut status = [0u8; 4];
+
+    skip_dummy_clock(raw, &mut status);
+
+    status
+  }
+
+  // RDDPM: bit 7 booster on, bit 5 idle, bit 4 partial, bit 3 sleep out,
+  // bit 2 normal mode, bit 1 display on
+  pub fn read_power_mode(&mut self) -> u8 {
+    self.read_register(DisplayCommand::RDDPM)
+  }
+
+  pub fn read_madctl(&mut self) -> u8 {
+    self.read_register(DisplayCommand::RDDMADCTL)
+  }
+
+  // RDDCOLMOD: the COLMOD value in use
+  pub fn read_pixel_format(&mut self) -> u8 {
+    self.read_register(DisplayCommand::RDDCOLMOD)
+  }
+
+  // RDDIM: bit 5 inversion on, bits 2-0 gamma curve
+  pub fn read_image_format(&mut self) -> u8 {
+    self.read_register(DisplayCommand::RDDIM)
+  }
+
+  fn read_register(&mut self, command: DisplayCommand) -> u8 {
+    let data = &mut [0u8; 1];
+
+    self.bus.read(command as u8, data);
+
+    data[0]
+  }
+
+  // Logs every readable status register in one go, to paste when reporting a
+  // panel showing garbage. Needs spi_miso, without it only a warning is logged
+  pub fn dump_diagnostics(&mut self) {
+    if self.pins.spi_miso.is_none() {
+      defmt::warn!("dump_diagnostics: no spi_miso pin, the panel registers can't be read");
+
+      return;
+    }
+
+    let id = self.read_display_id();
+    let status = self.read_display_status();
+
+    defmt::info!("Display ID: {=[u8]:#04x}", id);
+    defmt::info!("Display status: {=[u8]:#04x}", status);
+    defmt::info!("Power mode: {=u8:#04x}", self.read_power_mode());
+    defmt::info!("MADCTL: {=u8:#04x}", self.read_madctl());
+    defmt::info!("Pixel format: {=u8:#04x}", self.read_pixel_format());
+    defmt::info!("Image format: {=u8:#04x}", self.read_image_format());
+    defmt::info!("Self-diagnostic: {=u8:#04x}", self.read_self_diagnostic());
+  }
+
+  // Bring-up tool: writes a test pattern at increasing clocks and reads it back
+  // slowly with RAMRD, returning the fastest clock that stored it intact (the
+  // slowest one tried if none did). Needs spi_miso; without it the configured
+  // baudrate is returned untouched. The first pixels of the top row are
+  // overwritten and the SPI is left at the configured baudrate, so pass the
+  // result to the next boot (DisplaySpiData) with some margin
+  pub fn probe_max_baudrate(&mut self) -> HertzU32 {
+    if self.pins.spi_miso.is_none() {
+      return self.baudrate;
+    }
+
+    let pixels = DISPLAY_PROBE_PATTERN.len() / 2;
+    let bpp = self.bpp;
+    let mut best = HertzU32::from_raw(DISPLAY_PROBE_BAUDRATES[0]);
+
+    self.bus.abort_batch();
+
+    self.set_color_mode(DisplayColorMode::RGB65K | DisplayColorMode::BPP16);
+
+    for baudrate in DISPLAY_PROBE_BAUDRATES {
+      let actual = self.bus.interface().spi.set_baudrate(self.peripheral_clock, HertzU32::from_raw(baudrate));
+
+      self.bus.columns(0, pixels as u16 - 1);
+      self.bus.rows(0, 0);
+
+      self.send_command(DisplayCommand::RAMWR);
+
+      self.bus.data(&DISPLAY_PROBE_PATTERN);
+
+      self.bus.interface().spi.set_baudrate(self.peripheral_clock, HertzU32::from_raw(DISPLAY_READ_BAUDRATE));
+
+      self.bus.columns(0, pixels as u16 - 1);
+      self.bus.rows(0, 0);
+
+      // Memory reads come as 3 bytes (R, G, B, 6 bits each, MSB aligned) per
+      // pixel after one dummy clock
+      let raw = &mut [0u8; DISPLAY_PROBE_PATTERN.len() / 2 * 3 + 1];
+      let data = &mut [0u8; DISPLAY_PROBE_PATTERN.len() / 2 * 3];
+
+      self.bus.read(DisplayCommand::RAMRD as u8, raw);
+
+      skip_dummy_clock(raw, data);
+
+      let mut intact = true;
+
+      for pixel in 0..pixels {
+        let expected = (DISPLAY_PROBE_PATTERN[pixel * 2] as u16) << 8 | DISPLAY_PROBE_PATTERN[pixel * 2 + 1] as u16;
+
+        let red = (data[pixel * 3] >> 3) as u16;
+        let green = (data[pixel * 3 + 1] >> 2) as u16;
+        let blue = (data[pixel * 3 + 2] >> 3) as u16;
+
+        if red << 11 | green << 5 | blue != expected {
+          intact = false;
+        }
+      }
+
+      if !intact {
+        break;
+      }
+
+      best = actual;
+    }
+
+    self.bus.interface().spi.set_baudrate(self.peripheral_clock, self.baudrate);
+
+    self.set_bpp(bpp);
+
+    best
+  }
+
+  pub fn set_backlight(&mut self, on: bool) {
+    if on != self.backlight_active_low {
+      self.pins.backlight.set_high().unwrap();
+    }
+    else {
+      self.pins.backlight.set_low().unwrap();
+    }
+  }
+
+  // Needs a module whose backlight is driven by the controller (CABC capable);
+  // on modules with the backlight wired to a GPIO only set_backlight has effect
+  pub fn set_brightness(&mut self, brightness: u8) {
+    self.brightness = brightness;
+
+    self.write_brightness(brightness);
+  }
+
+  fn write_brightness(&mut self, brightness: u8) {
+    self.send_command(DisplayCommand::WRCTRLD);
+
+    self.bus.data(&[0b00101100]); // BCTRL | DD | BL
+
+    self.send_command(DisplayCommand::WRDISBV);
+
+    self.bus.data(&[brightness]);
+  }
+
+  // Turns the output and the backlight off, keeping the panel RAM and the
+  // framebuffer untouched, e.g. for an attract mode timeout. Unlike sleep it
+  // takes effect at once and unblank brings the same picture straight back.
+  // Calling either one twice in a row does nothing the second time
+  pub fn blank(&mut self) {
+    self.set_blanked(true);
+  }
+
+  pub fn unblank(&mut self) {
+    self.set_blanked(false);
+  }
+
+  fn set_blanked(&mut self, blank: bool) {
+    if let Some(backlight) = blank_output(&mut self.bus, &mut self.blanked, blank) {
+      self.display_on = !blank;
+
+      self.set_backlight(backlight);
+    }
+  }
+
+  // Ramps the brightness down to 0 in steps waiting step_ms after each, e.g.
+  // before swapping photos. The level set with set_brightness is kept (and
+  // still returned by brightness()) for fade_in to restore. Like
+  // set_brightness it needs a CABC capable module
+  pub fn fade_out(&mut self, steps: u16, delay: &mut impl DelayMs<u32>, step_ms: u32) {
+    let steps = steps.max(1) as u32;
+
+    for step in 1..=steps {
+      self.write_brightness((self.brightness as u32 * (steps - step) / steps) as u8);
+
+      delay.delay_ms(step_ms);
+    }
+  }
+
+  // Ramps the brightness up from 0 back to the level set with set_brightness
+  pub fn fade_in(&mut self, steps: u16, delay: &mut impl DelayMs<u32>, step_ms: u32) {
+    let steps = steps.max(1) as u32;
+
+    for step in 1..=steps {
+      self.write_brightness((self.brightness as u32 * step / steps) as u8);
+
+      delay.delay_ms(step_ms);
+    }
+  }
+
+  // VDVVRHEN: with enable the VDV and VRH voltages come from the VDVS/VRHS
+  // commands instead of the values programmed in NVM
+  pub fn enable_vdv_vrh_command(&mut self, enable: bool) {
+    self.send_command(DisplayCommand::VDVVRHEN);
+
+    self.bus.data(&[enable as u8, 0xFF]);
+  }
+
+  // VCMOFSET: offset 0x00..=0x3F moves VCOM from -0.8V to +0.775V in 25mV
+  // steps, 0x20 being no offset (the reset value). Flicker or uneven
+  // brightness is usually fixed a few steps away from 0x20 (e.g. 0x1C-0x24)
+  pub fn set_vcom_offset(&mut self, offset: u8) -> Result<(), DisplayError> {
+    if offset > 0x3F {
+      return Err(DisplayError::ParameterOutOfRange { value: offset, max: 0x3F });
+    }
+
+    self.send_command(DisplayCommand::VCMOFSET);
+
+    self.bus.data(&[offset]);
+
+    Ok(())
+  }
+
+  // GCTRL: bits 6-4 set VGH (0 is 12.2V .. 7 is 14.97V), bits 2-0 set VGL
+  // (0 is -7.16V .. 7 is -12.5V); bits 7 and 3 must be 0 (ReservedBitsSet).
+  // The reset value 0x35 (VGH 13.26V, VGL -10.43V) is what most vendor
+  // sequences send, 0x75 (VGH 14.97V) helps panels that ghost or look washed out
+  pub fn set_gate_control(&mut self, value: u8) -> Result<(), DisplayError> {
+    let value = gate_control(value)?;
+
+    self.send_command(DisplayCommand::GCTRL);
+
+    self.bus.data(&[value]);
+
+    Ok(())
+  }
+
+  // GTADJ: the gate on timing GTA (0x00..=0x3F) and the gate off timing
+  // GOF/GOFR (high/low nibble), sent after the fixed 0x2A and 0x2B keys. The
+  // reset values are 0x22 and 0x75
+  pub fn set_gate_timing_adjustment(&mut self, gate_on: u8, gate_off: u8) -> Result<(), DisplayError> {
+    let params = gate_timing_adjustment(gate_on, gate_off)?;
+
+    self.send_command(DisplayCommand::GTADJ);
+
+    self.bus.data(&params);
+
+    Ok(())
+  }
+
+  // Most modules don't need these. Some vendor variants store two register
+  // profiles and switch between them with REGSEL1/REGSEL2; the datasheet
+  // leaves their parameters to the panel vendor, so this just sends the bank
+  // number (0 or 1) to both: check the values the module init code expects
+  pub fn select_register_bank(&mut self, bank: u8) -> Result<(), DisplayError> {
+    if bank > 1 {
+      return Err(DisplayError::ParameterOutOfRange { value: bank, max: 1 });
+    }
+
+    self.send_command(DisplayCommand::REGSEL1);
+
+    self.bus.data(&[bank]);
+
+    self.send_command(DisplayCommand::REGSEL2);
+
+    self.bus.data(&[bank]);
+
+    Ok(())
+  }
+
+  // IDSET: the three ID bytes later returned by RDDID, for modules that tell
+  // their profiles apart by ID
+  pub fn set_id_code(&mut self, id: [u8; 3]) {
+    self.send_command(DisplayCommand::IDSET);
+
+    self.bus.data(&id);
+  }
+
+  // Renders every drawing color as its gray of the same luminance, e.g. for a
+  // calmer look while idle. Images sent as raw bytes (blit, send_data) are
+  // drawn as they are
+  pub fn set_grayscale(&mut self, on: bool) {
+    self.grayscale = on;
+  }
+
+  pub fn set_bpp12_nibble_order(&mut self, order: DisplayNibbleOrder) {
+    self.nibble_order = order;
+  }
+
+  // UNKNOWN configures the panel in BPP16 and is stored as BPP16, so the
+  // tracked mode always matches the panel and drawing keeps working
+  pub fn set_bpp(&mut self, bpp: DisplayColorModeBPP) {
+    let bpp = configured_bpp(bpp);
+
+    self.bpp = bpp;
+
+    self.set_color_mode(color_mode_bits(bpp));
+  }
+
+  fn set_color_mode(&mut self, mode: u8) {
+    self.send_command(DisplayCommand::COLMOD);
+
+    self.bus.data(&[mode]);
+  }
+
+  pub fn set_columns(&mut self, start: u16, end: u16) -> Result<(), DisplayError> {
+    check_range(DisplayAxis::X, start, end, self.width)?;
+
+    self.bus.columns(start, end);
+
+    Ok(())
+  }
+
+  pub fn set_display(&mut self, on: bool) {
+    self.display_on = on;
+
+    if on {
+      self.send_command(DisplayCommand::DISPON);
+    }
+    else {
+      self.send_command(DisplayCommand::DISPOFF);
+    }
+  }
+
+  pub fn set_inversion_mode(&mut self, on: bool) {
+    if on {
+      self.send_command(DisplayCommand::INVON);
+    }
+    else {
+      self.send_command(DisplayCommand::INVOFF);
+    }
+  }
+
+  pub fn set_normal_mode(&mut self) {
+    self.send_command(DisplayCommand::NORON);
+  }
+
+  // Width and height swap when the rotation changes between portrait and
+  // landscape (MADCTL::MV toggles); the resulting (width, height) is returned
+  pub fn set_rotation(&mut self, rotation: DisplayRotation) -> (u16, u16) {
+    (self.width, self.height) = rotation.resolution_from(self.rotation, self.width, self.height);
+
+    self.rotation = rotation;
+
+    self.apply_madctl();
+
+    self.resolution()
+  }
+
+  // Flips the column (MX) and page (MY) address order on top of the rotation
+  pub fn set_mirror(&mut self, x: bool, y: bool) {
+    self.madctl = self.madctl.mirror_x(x).mirror_y(y);
+
+    self.apply_madctl();
+  }
+
+  // For panels wired with the red and blue subpixels swapped
+  pub fn set_bgr(&mut self, on: bool) {
+    self.madctl = self.madctl.bgr(on);
+
+    self.apply_madctl();
+  }
+
+  // MH: refresh the panel columns right to left. Both latch order bits only
+  // change how the panel scans the memory, not where the pixels are written;
+  // some panels need them to avoid a flipped or reversed scan image
+  pub fn set_data_latch_order(&mut self, mh: bool) {
+    self.madctl = self.madctl.latch_order_reversed(mh);
+
+    self.apply_madctl();
+  }
+
+  // ML: refresh the panel lines bottom to top
+  pub fn set_line_address_order(&mut self, ml: bool) {
+    self.madctl = self.madctl.line_order_reversed(ml);
+
+    self.apply_madctl();
+  }
+
+  pub fn set_madctl(&mut self, madctl: Madctl) {
+    self.madctl = madctl;
+
+    self.apply_madctl();
+  }
+
+  pub fn madctl(&self) -> Madctl {
+    self.madctl
+  }
+
+  // Sends the rotation and every other MADCTL setting as a single byte
+  pub fn apply_madctl(&mut self) {
+    self.send_command(DisplayCommand::MADCTL);
+
+    self.bus.data(&[self.madctl.bits(self.rotation)]);
+  }
+
+  // Changes the color mode and the rotation together at runtime. Applied with
+  // the output off, so the panel never shows the memory read with the new
+  // settings halfway through; the single setters stay immediate
+  pub fn reconfigure(&mut self, bpp: DisplayColorModeBPP, rotation: DisplayRotation) -> (u16, u16) {
+    self.with_display_off(|display| {
+      display.set_bpp(bpp);
+
+      display.set_rotation(rotation)
+    })
+  }
+
+  // Runs a multi-step reconfiguration (only reconfigure for now) with DISPOFF,
+  // turning the output back on afterwards if it was on
+  fn with_display_off<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+    let was_on = self.display_on;
+
+    if was_on {
+      self.set_display(false);
+    }
+
+    let result = f(self);
+
+    if was_on {
+      self.set_display(true);
+    }
+
+    result
+  }
+
+  // RAMCTRL takes two parameters: the first selects the RAM access interface
+  // (bit 4, RM) and the display mode (bits 1-0, DM), the second holds the
+  // fixed bits 7-6 (always 1), the RGB expansion (bits 5-4, EPF), the byte
+  // order (bit 3, ENDIAN), the RGB interface width (bit 2, RIM) and the
+  // MCU data translation (bits 1-0, MDT). Reserved bits are forced to their
+  // required values; DISPLAY_RAM_CONTROL_DEFAULT is the reset value. The
+  // parameters are kept and sent again by reinit
+  pub fn set_ram_control(&mut self, params: [u8; 2]) {
+    let params = [params[0] & 0x13, params[1] | 0xC0];
+
+    self.ram_control = Some(params);
+
+    self.send_command(DisplayCommand::RAMCTRL);
+
+    self.bus.data(&params);
+  }
+
+  // Defines the vertically scrolling area as the scroll_height lines after the
+  // first top_fixed ones; the lines below it up to DISPLAY_RAM_LINES stay fixed.
+  // Scrolling runs along the panel gates, so it only moves the picture
+  // vertically in the rotations without MADCTL::MV (the portrait ones)
+  pub fn set_scroll_area(&mut self, top_fixed: u16, scroll_height: u16) {
+    let top_fixed = top_fixed.min(DISPLAY_RAM_LINES);
+    let scroll_height = scroll_height.min(DISPLAY_RAM_LINES - top_fixed);
+    let bottom_fixed = DISPLAY_RAM_LINES - top_fixed - scroll_height;
+
+    self.send_command(DisplayCommand::VSCRDEF);
+
+    self.bus.data(&[
+      (top_fixed >> 8) as u8,
+      (top_fixed & 0xFF) as u8,
+      (scroll_height >> 8) as u8,
+      (scroll_height & 0xFF) as u8,
+      (bottom_fixed >> 8) as u8,
+      (bottom_fixed & 0xFF) as u8
+    ]);
+  }
+
+  // Frame memory line shown at the top of the scrolling area, counted from the
+  // top of the memory (so it starts at top_fixed)
+  pub fn set_scroll_start(&mut self, line: u16) {
+    self.send_command(DisplayCommand::VSCSAD);
+
+    self.bus.data(&[(line >> 8) as u8, (line & 0xFF) as u8]);
+  }
+
+  // Slides a full screen image (width * height pixels in the active color mode)
+  // over the current one with the hardware vertical scroll, in steps waiting
+  // step_ms after each. The scroll area is set to the screen height, so the
+  // frame memory line L always ends up holding the image row L: the lines that
+  // scroll out on one side wrap around to the other one and get the new rows,
+  // and after the whole height the scroll start is back at 0. Like any vertical
+  // scroll it needs a portrait rotation; with a framebuffer it just writes it
+  pub fn transition_scroll(&mut self, new_image: &[u8], direction: DisplayTransitionDirection, steps: u16, delay: &mut impl DelayMs<u32>, step_ms: u32) {
+    let row_bytes = self.width as usize * self.bytes_per_pixel();
+    let height = (new_image.len() / row_bytes.max(1)).min(self.height as usize) as u16;
+
+    if row_bytes == 0 || height == 0 {
+      return;
+    }
+
+    if self.framebuffer.is_some() {
+      self.open_window(0, 0, self.width - 1, height - 1);
+      self.send_data(&new_image[..height as usize * row_bytes]);
+
+      return;
+    }
+
+    let step_lines = height.div_ceil(steps.max(1));
+
+    self.set_scroll_area(0, height);
+
+    let mut shown = 0u16;
+
+    while shown < height {
+      let lines = step_lines.min(height - shown);
+
+      // First and last line (frame memory and image row) entering the screen
+      let (first, scroll_start) = match direction {
+        DisplayTransitionDirection::FromTop    => (height - shown - lines, height - shown - lines),
+        DisplayTransitionDirection::FromBottom => (shown, (shown + lines) % height)
+      };
+
+      let last = first + lines - 1;
+
+      self.set_scroll_start(scroll_start);
+
+      self.open_window(0, first, self.width - 1, last);
+      self.send_data(&new_image[first as usize * row_bytes..(last as usize + 1) * row_bytes]);
+
+      shown += lines;
+
+      delay.delay_ms(step_ms);
+    }
+
+    self.set_scroll_area(0, DISPLAY_RAM_LINES);
+    self.set_scroll_start(0);
+  }
+
+  pub fn set_sleep_mode(&mut self, on: bool) {
+    self.sleeping = on;
+
+    if on {
+      self.send_command(DisplayCommand::SLPIN);
+    }
+    else {
+      self.send_command(DisplayCommand::SLPOUT);
+    }
+  }
+
+  pub fn set_rows(&mut self, start: u16, end: u16) -> Result<(), DisplayError> {
+    check_range(DisplayAxis::Y, start, end, self.height)?;
+
+    self.bus.rows(start, end);
+
+    Ok(())
+  }
+
+  pub fn set_text_background_color(&mut self, color: Option<u32>) {
+    self.text.background_color = color;
+  }
+
+  pub fn set_text_direction(&mut self, direction: DisplayTextDirection) {
+    self.text.direction = direction;
+  }
+
+  pub fn set_text_foreground_color(&mut self, color: u32) {
+    self.text.foreground_color = color;
+  }
+
+  // Outlines every glyph pixel with (color, thickness) before drawing it, for
+  // text over busy images. Each glyph pixel then costs one more rect, so it
+  // roughly doubles the drawing time; None turns it off. The thickness is
+  // clamped to DISPLAY_TEXT_MAX_OUTLINE
+  pub fn set_text_outline(&mut self, outline: Option<(u32, u16)>) {
+    self.text.outline = outline.map(|(color, thickness)| (color, thickness.min(DISPLAY_TEXT_MAX_OUTLINE)));
+  }
+
+  // The pixel sizes are clamped as TextScale does
+  pub fn set_text_pixel_height(&mut self, pixel_height: u16) {
+    self.text.scale = TextScale::new(self.text.scale.pixel_width(), pixel_height);
+  }
+
+  pub fn set_text_pixel_width(&mut self, pixel_width: u16) {
+    self.text.scale = TextScale::new(pixel_width, self.text.scale.pixel_height());
+  }
+
+  // Same pixel width and height, keeping the 1:1 glyph aspect ratio
+  pub fn set_text_scale(&mut self, scale: u16) {
+    self.text.scale = TextScale::uniform(scale);
+  }
+
+  // Nothing is sent unless the whole window is valid, so a bad window never
+  // leaves the panel waiting for data with stale addresses
+  pub fn set_window(&mut self, start_x: u16, start_y: u16, end_x: u16, end_y: u16) -> Result<(), DisplayError> {
+    check_window(start_x, start_y, end_x, end_y, self.width, self.height)?;
+
+    self.open_window(start_x, start_y, end_x, end_y);
+
+    Ok(())
+  }
+
+  // An empty rect has no last pixel and so no window, it's an InvalidRange on
+  // the axis with no size. One ending past the u16 coordinates is OutOfBounds
+  pub fn set_window_rect(&mut self, rect: Rect) -> Result<(), DisplayError> {
+    match rect.end() {
+      Some(end) => self.set_window(rect.x, rect.y, end.x, end.y),
+      None if rect.width == 0 => Err(DisplayError::InvalidRange { axis: DisplayAxis::X, start: rect.x, end: rect.x.wrapping_sub(1) }),
+      None if rect.height == 0 => Err(DisplayError::InvalidRange { axis: DisplayAxis::Y, start: rect.y, end: rect.y.wrapping_sub(1) }),
+      None if rect.x.checked_add(rect.width - 1).is_none() => Err(DisplayError::OutOfBounds { axis: DisplayAxis::X, value: u16::MAX, max: self.width.saturating_sub(1) }),
+      None => Err(DisplayError::OutOfBounds { axis: DisplayAxis::Y, value: u16::MAX, max: self.height.saturating_sub(1) })
+    }
+  }
+
+  fn open_window(&mut self, start_x: u16, start_y: u16, end_x: u16, end_y: u16) {
+    self.window_first_color = None;
+    self.window_pixels.open(start_x, start_y, end_x, end_y);
+
+    #[cfg(debug_assertions)]
+    {
+      self.window_bpp = self.bytes_per_pixel();
+    }
+
+    if let Some(framebuffer) = self.framebuffer.as_mut() {
+      framebuffer.set_window(start_x, start_y, end_x, end_y);
+
+      return;
+    }
+
+    self.bus.window(start_x, start_y, end_x, end_y);
+  }
+
+  // Pixels send_data still has to write to fill the window last set exactly,
+  // 0 once it is full. The panel wraps around to the window start after the
+  // last pixel, so 0 after a stream that should have left some, or anything
+  // but 0 after one that should have filled it, means a wrong byte count
+  pub fn remaining_window_pixels(&self) -> u32 {
+    self.window_pixels.remaining()
+  }
+
+  pub fn soft_reset(&mut self, delay: &mut impl DelayMs<u32>) {
+    self.send_command(DisplayCommand::SWRESET);
+
+    self.display_on = false;
+    self.sleeping = true;
+
+    delay.delay_ms(150);
+  }
+
+  pub fn bytes_per_pixel(&self) -> usize {
+    bytes_per_pixel(self.bpp)
+  }
+
+  // While a framebuffer is set, set_window and send_data (and so every drawing
+  // method) write into it instead of the panel, and `present` flushes it. It
+  // must hold width * height * bytes_per_pixel bytes; the previous one is returned.
+  pub fn set_framebuffer(&mut self, data: Option<&'static mut [u8]>) -> Option<&'static mut [u8]> {
+    let previous = self.framebuffer.take().map(|framebuffer| framebuffer.data);
+
+    self.framebuffer = data.map(|data| ScreenBuffer::new(data, self.width, self.height));
+
+    previous
+  }
+
+  fn write_framebuffer(&mut self, data: &[u8]) {
+    let bytes_per_pixel = self.bytes_per_pixel();
+
+    if let Some(framebuffer) = self.framebuffer.as_mut() {
+      framebuffer.write(data, self.width, bytes_per_pixel);
+    }
+  }
+
+  // Like copy_region_to, but read back from the panel memory with RAMRD, so it
+  // works without a framebuffer; needs spi_miso. The panel always returns
+  // 18 bit pixels (3 bytes, R, G and B with 6 bits each, MSB aligned) whatever
+  // the write color mode, and they are converted here: kept as they are in
+  // BPP18, truncated to 5-6-5 in BPP16M and packed to 5-6-5 words in BPP16.
+  // BPP12 can't be read back (UnsupportedColorMode). The SPI runs at
+  // DISPLAY_READ_BAUDRATE meanwhile
+  pub fn read_region(&mut self, x: u16, y: u16, width: u16, height: u16, dst: &mut [u8]) -> Result<(), DisplayError> {
+    if self.pins.spi_miso.is_none() {
+      return Err(DisplayError::NoReadPin);
+    }
+
+    let bytes_per_pixel = self.bytes_per_pixel();
+    let needed = region_bytes(self.bpp, width, height)?;
+
+    if dst.len() < needed {
+      return Err(DisplayError::BufferTooSmall { needed, length: dst.len() });
+    }
+
+    if needed == 0 {
+      return Ok(());
+    }
+
+    check_range(DisplayAxis::X, x, x.saturating_add(width - 1), self.width)?;
+    check_range(DisplayAxis::Y, y, y.saturating_add(height - 1), self.height)?;
+
+    self.bus.abort_batch();
+
+    self.bus.interface().spi.set_baudrate(self.peripheral_clock, HertzU32::from_raw(DISPLAY_READ_BAUDRATE));
+
+    let raw = &mut [0u8; DISPLAY_READ_CHUNK * 3 + 1];
+    let data = &mut [0u8; DISPLAY_READ_CHUNK * 3];
+    let mut offset = 0;
+
+    for row in y..y + height {
+      let mut column = x;
+
+      while column < x + width {
+        let pixels = ((x + width - column) as usize).min(DISPLAY_READ_CHUNK);
+
+        self.bus.columns(column, column + pixels as u16 - 1);
+        self.bus.rows(row, row);
+
+        self.bus.read(DisplayCommand::RAMRD as u8, &mut raw[..pixels * 3 + 1]);
+
+        skip_dummy_clock(&raw[..pixels * 3 + 1], &mut data[..pixels * 3]);
+
+        for pixel in data[..pixels * 3].chunks(3) {
+          let (red, green, blue) = (pixel[0], pixel[1], pixel[2]);
+
+          match self.bpp {
+            DisplayColorModeBPP::BPP16 => {
+              let color = ((red >> 3) as u16) << 11 | ((green >> 2) as u16) << 5 | (blue >> 3) as u16;
+
+              dst[offset..offset + 2].copy_from_slice(&color.to_be_bytes());
+            },
+            DisplayColorModeBPP::BPP16M => dst[offset..offset + 3].copy_from_slice(&[red & 0xF8, green & 0xFC, blue & 0xF8]),
+            _                           => dst[offset..offset + 3].copy_from_slice(&[red, green, blue])
+          }
+
+          offset += bytes_per_pixel;
+        }
+
+        column += pixels as u16;
+      }
+    }
+
+    self.bus.interface().spi.set_baudrate(self.peripheral_clock, self.baudrate);
+
+    Ok(())
+  }
+
+  // Copies the pixels of a region of the framebuffer into dst, row by row in
+  // the bytes of the active color mode, so it can be put back later with
+  // draw_image (e.g. what is behind a popup). dst must hold at least
+  // width * height * bytes_per_pixel bytes
+  pub fn copy_region_to(&self, x: u16, y: u16, width: u16, height: u16, dst: &mut [u8]) -> Result<(), DisplayError> {
+    let framebuffer = self.framebuffer.as_ref().ok_or(DisplayError::NoFramebuffer)?;
+    let needed = region_bytes(self.bpp, width, height)?;
+
+    if width == 0 || height == 0 {
+      return Ok(());
+    }
+
+    check_range(DisplayAxis::X, x, x.saturating_add(width - 1), self.width)?;
+    check_range(DisplayAxis::Y, y, y.saturating_add(height - 1), self.height)?;
+
+    if dst.len() < needed {
+      return Err(DisplayError::BufferTooSmall { needed, length: dst.len() });
+    }
+
+    framebuffer.copy_region(Rect::new(x, y, width, height), self.width, self.bytes_per_pixel(), dst);
+
+    Ok(())
+  }
+
+  // Draws width * height pixels already in the bytes of the active color mode,
+  // e.g. saved with copy_region_to
+  pub fn draw_image(&mut self, x: u16, y: u16, width: u16, height: u16, data: &[u8]) -> Result<(), DisplayError> {
+    let needed = region_bytes(self.bpp, width, height)?;
+
+    if width == 0 || height == 0 {
+      return Ok(());
+    }
+
+    if data.len() < needed {
+      return Err(DisplayError::BufferTooSmall { needed, length: data.len() });
+    }
+
+    check_range(DisplayAxis::X, x, x.saturating_add(width - 1), self.width)?;
+    check_range(DisplayAxis::Y, y, y.saturating_add(height - 1), self.height)?;
+
+    self.stream_image(x, y, width, height, &data[..needed]);
+
+    Ok(())
+  }
+
+  // Checked set_window + send_data in one call: data must be exactly the
+  // width * height pixels of the region in the active color mode
+  pub fn blit(&mut self, x: u16, y: u16, width: u16, height: u16, data: &[u8]) -> Result<(), DisplayError> {
+    let expected = region_bytes(self.bpp, width, height)?;
+
+    if data.len() != expected {
+      return Err(DisplayError::LengthMismatch { expected, length: data.len() });
+    }
+
+    if expected == 0 {
+      return Ok(());
+    }
+
+    check_range(DisplayAxis::X, x, x.saturating_add(width - 1), self.width)?;
+    check_range(DisplayAxis::Y, y, y.saturating_add(height - 1), self.height)?;
+
+    self.stream_image(x, y, width, height, data);
+
+    Ok(())
+  }
+
+  // Like blit with new, but only the pixels that differ from old (the image
+  // currently shown there) are sent: each row is split in runs of changed
+  // pixels, joining runs whose gap costs less to resend than a new window
+  pub fn draw_image_delta(&mut self, x: u16, y: u16, width: u16, height: u16, old: &[u8], new: &[u8]) -> Result<(), DisplayError> {
+    let expected = region_bytes(self.bpp, width, height)?;
+    let bytes_per_pixel = self.bytes_per_pixel();
+
+    if new.len() != expected {
+      return Err(DisplayError::LengthMismatch { expected, length: new.len() });
+    }
+
+    if old.len() != expected {
+      return Err(DisplayError::LengthMismatch { expected, length: old.len() });
+    }
+
+    if expected == 0 {
+      return Ok(());
+    }
+
+    check_range(DisplayAxis::X, x, x.saturating_add(width - 1), self.width)?;
+    check_range(DisplayAxis::Y, y, y.saturating_add(height - 1), self.height)?;
+
+    let max_gap = DISPLAY_WINDOW_OVERHEAD / bytes_per_pixel;
+    let row_bytes = width as usize * bytes_per_pixel;
+
+    for row in 0..height as usize {
+      let old_row = &old[row * row_bytes..(row + 1) * row_bytes];
+      let new_row = &new[row * row_bytes..(row + 1) * row_bytes];
+
+      for (start, end) in changed_runs(old_row, new_row, bytes_per_pixel, max_gap) {
+        let run_width = (end - start + 1) as u16;
+
+        self.stream_image(x + start as u16, y + row as u16, run_width, 1, &new_row[start * bytes_per_pixel..(end + 1) * bytes_per_pixel]);
+      }
+    }
+
+    Ok(())
+  }
+
+  pub fn blit_rect(&mut self, rect: Rect, data: &[u8]) -> Result<(), DisplayError> {
+    self.blit(rect.x, rect.y, rect.width, rect.height, data)
+  }
+
+  // Sends the part of the image inside the clip, row by row when it is cut
+  fn stream_image(&mut self, x: u16, y: u16, width: u16, height: u16, data: &[u8]) {
+    let (clip_x, clip_y, clip_width, clip_height) = match self.clip_region(x, y, width, height) {
+      Some(region) => region,
+      None         => return
+    };
+
+    self.open_window(clip_x, clip_y, clip_x + clip_width - 1, clip_y + clip_height - 1);
+
+    if (clip_x, clip_y, clip_width, clip_height) == (x, y, width, height) {
+      self.send_data(data);
+
+      return;
+    }
+
+    let bytes_per_pixel = self.bytes_per_pixel();
+    let row_bytes = clip_width as usize * bytes_per_pixel;
+
+    for row in clip_y..clip_y + clip_height {
+      let start = ((row - y) as usize * width as usize + (clip_x - x) as usize) * bytes_per_pixel;
+
+      self.send_data(&data[start..start + row_bytes]);
+    }
+  }
+
+  // Draws width * height pixels of 3 bytes (R, G, B) each, converted to the
+  // active color mode while streaming, so one asset serves every mode
+  pub fn draw_image_rgb888(&mut self, x: u16, y: u16, width: u16, height: u16, data: &[u8]) -> Result<(), DisplayError> {
+    let needed = width as usize * height as usize * 3;
+
+    if data.len() < needed {
+      return Err(DisplayError::BufferTooSmall { needed, length: data.len() });
+    }
+
+    let bpp = self.bpp;
+
+    self.fill_with(x, y, width, height, |pixel_x, pixel_y| {
+      let start = ((pixel_y - y) as usize * width as usize + (pixel_x - x) as usize) * 3;
+
+      rgb888_to_color(bpp, data[start], data[start + 1], data[start + 2])
+    });
+
+    Ok(())
+  }
+
+  // Draws an indexed image (width * height bytes, each a palette index) with
+  // the colors the palette maps them to right now; redraw it after each cycle
+  pub fn draw_palette_cycled_image<const N: usize>(&mut self, x: u16, y: u16, width: u16, height: u16, indices: &[u8], palette: &Palette<N>) -> Result<(), DisplayError> {
+    let expected = width as usize * height as usize;
+
+    if indices.len() != expected {
+      return Err(DisplayError::LengthMismatch { expected, length: indices.len() });
+    }
+
+    self.fill_with(x, y, width, height, |pixel_x, pixel_y| {
+      palette.color(indices[(pixel_y - y) as usize * width as usize + (pixel_x - x) as usize])
+    });
+
+    Ok(())
+  }
+
+  // Expands a 1 bit per pixel bitmap (e.g. text from another font renderer):
+  // rows of width.div_ceil(8) bytes, bit 7 being the leftmost pixel like in FONT,
+  // set bits drawn in foreground_color and clear ones in background_color
+  pub fn blit_1bpp(&mut self, rect: Rect, bits: &[u8], foreground_color: u32, background_color: u32) -> Result<(), DisplayError> {
+    let needed = bitmap_row_bytes(rect.width) * rect.height as usize;
+
+    if bits.len() < needed {
+      return Err(DisplayError::BufferTooSmall { needed, length: bits.len() });
+    }
+
+    self.fill_with(rect.x, rect.y, rect.width, rect.height, |pixel_x, pixel_y| {
+      if bitmap_bit(bits, rect.width, pixel_x - rect.x, pixel_y - rect.y) {
+        foreground_color
+      }
+      else {
+        background_color
+      }
+    });
+
+    Ok(())
+  }
+
+  // Streams a whole screen of pixels; data must be exactly
+  // width * height * bytes_per_pixel bytes
+  pub fn present_frame(&mut self, data: &[u8]) -> Result<(), DisplayError> {
+    self.blit(0, 0, self.width, self.height, data)
+  }
+
+  // Streams a whole screen produced one row at a time (width pixels in the
+  // active color mode each), e.g. decoded or generated scanlines that don't fit
+  // in RAM as a frame. Rows past the screen height are left unread; a row of
+  // the wrong length stops the frame there with the rows before it drawn.
+  // Like send_data it writes straight to the window and ignores the clip
+  pub fn present_rows<I: Iterator<Item = R>, R: AsRef<[u8]>>(&mut self, rows: I) -> Result<(), DisplayError> {
+    let row_bytes = region_bytes(self.bpp, self.width, 1)?;
+
+    if row_bytes == 0 {
+      return Ok(());
+    }
+
+    self.open_window(0, 0, self.width - 1, self.height - 1);
+
+    stream_rows(rows, row_bytes, self.height, |row| self.send_data(row))
+  }
+
+  // Complements the colors of a region of the framebuffer, e.g. to highlight a
+  // selected item over any background. Without a framebuffer the current colors
+  // can't be read back, so it does nothing
+  pub fn invert_region(&mut self, x: u16, y: u16, width: u16, height: u16) {
+    let (x, y, width, height) = match self.clip_region(x, y, width, height) {
+      Some(region) => region,
+      None         => return
+    };
+
+    let bpp = self.bpp;
+    let bytes_per_pixel = self.bytes_per_pixel();
+    let display_width = self.width as usize;
+
+    let framebuffer = match self.framebuffer.as_mut() {
+      Some(framebuffer) => framebuffer,
+      None              => return
+    };
+
+    for row in y..y + height {
+      let start = (row as usize * display_width + x as usize) * bytes_per_pixel;
+      let end = start + width as usize * bytes_per_pixel;
+
+      if let Some(pixels) = framebuffer.data.get_mut(start..end) {
+        invert_pixels(bpp, pixels);
+      }
+    }
+  }
+
+  // Waits for the start of the next vertical blanking (rising edge of TE) and
+  // streams the whole framebuffer. The wait takes up to one refresh period,
+  // ~16.7ms at the default 60Hz, and gives up after DISPLAY_TE_TIMEOUT_US for
+  // each edge when TE isn't toggling. Note a full 16bpp frame at 30MHz takes ~31ms
+  // to send, longer than a refresh, so the frame rate (FRCTRL2) must be lowered
+  // for the write to stay ahead of the scan on the whole screen. BPP12 frames
+  // can't be buffered, so it returns UnsupportedColorMode then
+  pub fn present(&mut self) -> Result<(), DisplayError> {
+    let length = region_bytes(self.bpp, self.width, self.height)?;
+
+    let framebuffer = self.framebuffer.take().ok_or(DisplayError::NoFramebuffer)?;
+    let length = length.min(framebuffer.data.len());
+
+    self.wait_for_vertical_blanking();
+
+    self.bus.window(0, 0, self.width - 1, self.height - 1);
+
+    self.write_pixel_data(&framebuffer.data[..length]);
+
+    self.framebuffer = Some(framebuffer);
+
+    Ok(())
+  }
+
+  pub fn set_tearing_effect(&mut self, on: bool) {
+    self.tearing_effect = on;
+
+    if on {
+      self.send_command(DisplayCommand::TEON);
+
+      self.bus.data(&[0x00]); // V-blanking information only
+    }
+    else {
+      self.send_command(DisplayCommand::TEOFF);
+    }
+  }
+
+  fn wait_for_vertical_blanking(&mut self) {
+    if !self.tearing_effect {
+      return;
+    }
+
+    let polls = microseconds_to_cycles(DISPLAY_TE_TIMEOUT_US, self.peripheral_clock) / DISPLAY_TE_POLL_CYCLES;
+
+    if let Some(tearing_effect) = self.pins.tearing_effect.as_ref() {
+      if !wait_for_level(polls, || tearing_effect.is_low().unwrap()) {
+        return;
+      }
+
+      wait_for_level(polls, || tearing_effect.is_high().unwrap());
+    }
+  }
+
+  pub fn brightness(&self) -> u8 {
+    self.brightness
+  }
+
+  pub fn char_height(&self) -> u16 {
+    self.text.scale.char_height()
+  }
+
+  pub fn char_width(&self) -> u16 {
+    self.text.scale.char_width()
+  }
+
+  pub fn draw_char(&mut self, x: u16, y: u16, c: char) {
+    self.draw_glyph(x, y, c as usize, self.text.background_color);
+  }
+
+  // Glyphs out of FONT (chars past U+00FF) draw nothing
+  fn draw_glyph(&mut self, x: u16, y: u16, index: usize, background_color: Option<u32>) {
+    let style = self.text;
+
+    draw_glyph(self, x, y, index, &style, background_color);
+  }
+
+  pub fn draw_number(&mut self, x: u16, y: u16, value: u32) {
+    let buf = &mut [0u8; U32_MAX_LENGTH];
+
+    self.draw_str(x, y, format_u32_into(buf, value));
+  }
+
+  // Right aligned in min_width characters (at most U32_MAX_LENGTH) padded with
+  // pad, e.g. b' ' or b'0', so the readout keeps its position and width
+  pub fn draw_number_padded(&mut self, x: u16, y: u16, value: u32, min_width: usize, pad: u8) {
+    let buf = &mut [0u8; U32_MAX_LENGTH];
+
+    self.draw_str(x, y, format_u32_padded_into(buf, value, min_width.min(U32_MAX_LENGTH), pad));
+  }
+
+  // Each byte is a FONT index as-is, with no UTF-8 decoding and no line breaks
+  // (0x0A is a glyph too), for raw data or code page style custom fonts
+  pub fn draw_bytes(&mut self, x: u16, y: u16, bytes: &[u8]) {
+    let style = self.text;
+
+    draw_bytes(self, x, y, bytes, &style);
+  }
+
+  // Vertical bars growing up from the bottom of the rect, value max filling
+  // its whole height (bigger values are capped). Bars share the width evenly;
+  // with more values than pixel columns one value per column is sampled. Only
+  // the bars are drawn, clear the rect first when redrawing
+  pub fn draw_bar_chart(&mut self, rect: Rect, values: &[u16], max: u16, color: u32) {
+    if values.is_empty() || max == 0 || rect.is_empty() {
+      return;
+    }
+
+    let bars = values.len().min(rect.width as usize);
+
+    for bar in 0..bars {
+      let bar_rect = bar_chart_bar(rect, bars, bar, values[bar * values.len() / bars], max);
+
+      self.draw_solid_rect(bar_rect.x, bar_rect.y, bar_rect.width, bar_rect.height, color);
+    }
+  }
+
+  // Filled box with an optional 1 pixel border and the text centered inside,
+  // clipped to the inside of the border when it doesn't fit. The text colors
+  // are only used for the label, the text settings are left as they were
+  pub fn draw_label(&mut self, rect: Rect, text: &str, foreground_color: u32, background_color: u32, border_color: Option<u32>) -> Result<(), DisplayError> {
+    if rect.is_empty() {
+      return Ok(());
+    }
+
+    let (inner, (text_x, text_y)) = self.text.scale.label_layout(rect, border_color.is_some(), text);
+
+    match border_color {
+      Some(border_color) => {
+        self.draw_solid_rect(rect.x, rect.y, rect.width, rect.height, border_color);
+        self.draw_solid_rect(inner.x, inner.y, inner.width, inner.height, background_color);
+      },
+      None => self.draw_solid_rect(rect.x, rect.y, rect.width, rect.height, background_color)
+    }
+
+    self.push_clip(inner)?;
+
+    let text_background_color = self.text.background_color;
+    let text_foreground_color = self.text.foreground_color;
+
+    self.text.background_color = None;
+    self.text.foreground_color = foreground_color;
+
+    self.draw_str(text_x, text_y, text);
+
+    self.text.background_color = text_background_color;
+    self.text.foreground_color = text_foreground_color;
+
+    self.pop_clip();
+
+    Ok(())
+  }
+
+  pub fn draw_str_at(&mut self, origin: Point, text: &str) {
+    self.draw_str(origin.x, origin.y, text);
+  }
+
+  // Lays the text out inside the rect: words wrap to the rect width (a word
+  // longer than a line is split), '\n' starts a new line and each line is
+  // aligned on its own. Lines that don't fit in the rect height (or would
+  // start past u16::MAX) are left out and false is returned, true when all
+  // the text was drawn
+  pub fn draw_paragraph(&mut self, rect: Rect, text: &str, align: DisplayTextAlign) -> bool {
+    let char_width = self.char_width();
+    let char_height = self.char_height();
+    let columns = (rect.width / char_width) as usize;
+
+    if columns == 0 || rect.height < char_height {
+      return text.is_empty();
+    }
+
+    if self.push_clip(rect).is_err() {
+      return false;
+    }
+
+    let rows = (rect.height / char_height) as usize;
+
+    let fits = wrap_paragraph(text, columns, rows, |row, line| {
+      let line_width = saturating_count(line.chars().count()).saturating_mul(char_width);
+
+      let y = match (row as u16).checked_mul(char_height).and_then(|offset| rect.y.checked_add(offset)) {
+        Some(y) => y,
+        None    => return false
+      };
+
+      self.draw_str(align.line_x(rect.x, rect.width, line_width), y, line);
+
+      true
+    });
+
+    self.pop_clip();
+
+    fits
+  }
+
+  pub fn draw_text(&mut self, x: u16, y: u16, text: String<31>) {
+    self.draw_str(x, y, text.as_str());
+  }
+
+  pub fn draw_text_anchored(&mut self, x: u16, y: u16, text: &str, anchor: DisplayTextAnchor) {
+    let (origin_x, origin_y) = self.text_origin(x, y, text, anchor);
+
+    self.draw_str(origin_x, origin_y, text);
+  }
+
+  // With a background color each line is filled in a single window first and
+  // the glyphs only draw their foreground pixels on top of it
+  //
+  // (x, y) is the top-left corner of the text box (see measure_text) in both
+  // directions; right to left the lines are laid from the right edge of the box
+  pub fn draw_str(&mut self, x: u16, y: u16, text: &str) {
+    let char_width = self.char_width();
+    let char_height = self.char_height();
+    let right_to_left = self.text.direction == DisplayTextDirection::RightToLeft;
+    let (box_width, _) = self.measure_text(text);
+
+    let mut render_y = y;
+
+    // Lines and glyphs past the screen are left out; the math saturates so a
+    // long line at a big scale is clipped instead of overflowing
+    for line in text.split('\n') {
+      if render_y >= self.height {
+        break;
+      }
+
+      let line_width = saturating_count(line.chars().count()).saturating_mul(char_width);
+      let line_x = if right_to_left {
+        x.saturating_add(box_width - line_width)
+      }
+      else {
+        x
+      };
+
+      if let Some(background_color) = self.text.background_color {
+        if line_width > 0 {
+          self.draw_solid_rect(line_x, render_y, line_width, char_height, background_color);
+        }
+      }
+
+      for (glyph_x, c) in self.text.scale.line_glyphs(x, box_width, line, self.text.direction, self.width) {
+        self.draw_glyph(glyph_x, render_y, c as usize, None);
+      }
+
+      render_y = render_y.saturating_add(char_height);
+    }
+  }
+
+  pub fn height(&self) -> u16 {
+    self.height
+  }
+
+  // (width, height) in the current rotation
+  pub fn resolution(&self) -> (u16, u16) {
+    (self.width, self.height)
+  }
+
+  // Saturates at u16::MAX for text bigger than that
+  pub fn measure_text(&self, text: &str) -> (u16, u16) {
+    self.text.scale.measure(text)
+  }
+
+  // Characters of a single line that fit in max_width pixels (all glyphs are
+  // char_width wide), e.g. to cut a label and append "...". It counts chars,
+  // not bytes: cut at text.char_indices().nth(count) for non-ASCII text
+  pub fn fit_text(&self, text: &str, max_width: u16) -> usize {
+    self.text.scale.fit(text, max_width)
+  }
+
+  // Largest set_text_scale (up to DISPLAY_TEXT_MAX_PIXEL_SIZE) at which the
+  // text fits in max_width x max_height pixels, 0 when not even scale 1 does
+  pub fn best_text_scale(&self, text: &str, max_width: u16, max_height: u16) -> u16 {
+    let (width, height) = self.measure_text(text);
+
+    let columns = width / self.char_width();
+    let rows = height / self.char_height();
+
+    font::scale_to_fit(columns, rows, max_width, max_height).min(DISPLAY_TEXT_MAX_PIXEL_SIZE)
+  }
+
+  // Top-left corner where draw_str must start so the text sits at (x, y)
+  // according to the anchor, clamped to the screen origin
+  pub fn text_origin(&self, x: u16, y: u16, text: &str, anchor: DisplayTextAnchor) -> (u16, u16) {
+    self.text.scale.origin(x, y, text, anchor)
+  }
+
+  pub fn is_blanked(&self) -> bool {
+    self.blanked
+  }
+
+  pub fn is_display_on(&self) -> bool {
+    self.display_on
+  }
+
+  pub fn is_sleeping(&self) -> bool {
+    self.sleeping
+  }
+
+  pub fn width(&self) -> u16 {
     self.width
   }
 }
+
+impl<const BUFFER_SIZE: usize> DrawTarget for Display<BUFFER_SIZE> {
+  fn size(&self) -> (u16, u16) {
+    self.resolution()
+  }
+
+  fn draw_solid_rect(&mut self, x: u16, y: u16, width: u16, height: u16, color: u32) {
+    Display::draw_solid_rect(self, x, y, width, height, color);
+  }
+}
+
+// Busy waits are counted in core cycles; the peripheral clock runs from the
+// system clock in the default clock setup, so its frequency is used for both
+fn microseconds_to_cycles(microseconds: u32, clock: HertzU32) -> u32 {
+  (microseconds as u64 * clock.to_Hz() as u64 / 1_000_000).min(u32::MAX as u64) as u32
+}
+
+// Polls until the condition holds, false when it didn't after polls tries
+fn wait_for_level(polls: u32, mut condition: impl FnMut() -> bool) -> bool {
+  for _ in 0..polls {
+    if condition() {
+      return true;
+    }
+
+    cortex_m::asm::delay(DISPLAY_TE_POLL_CYCLES);
+  }
+
+  condition()
+}
+
+// Multi-byte reads come one dummy clock late, so every byte is split between
+// two of the bytes clocked in; raw must be one byte longer than data
+fn skip_dummy_clock(raw: &[u8], data: &mut [u8]) {
+  for (index, byte) in data.iter_mut().enumerate() {
+    *byte = raw[index] << 1 | raw[index + 1] >> 7;
+  }
+}
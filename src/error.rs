@@ -0,0 +1,47 @@
+// Errors of the Display methods and the range checks behind them
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DisplayAxis {
+  X,
+  Y
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DisplayError {
+  OutOfBounds { axis: DisplayAxis, value: u16, max: u16 },  // value is past the last pixel (max)
+  InvalidRange { axis: DisplayAxis, start: u16, end: u16 }, // start is after end
+  BufferTooSmall { needed: usize, length: usize },          // the buffer can't hold the pixels of the region
+  LengthMismatch { expected: usize, length: usize },        // the data isn't exactly the pixels of the region
+  NoFramebuffer,                                            // reading pixels back needs a framebuffer
+  ClipStackFull,                                            // more than DISPLAY_CLIP_DEPTH nested push_clip
+  ParameterOutOfRange { value: u8, max: u8 },               // a register parameter is past its largest value
+  ReservedBitsSet { value: u8, mask: u8 },                  // a register parameter sets bits of mask that must be 0
+  NoReadPin,                                                // reading the panel needs spi_miso
+  UnsupportedColorMode                                      // BPP12 packs pixel pairs, which can't be counted per pixel
+}
+
+// start..=end must be inside 0..size and in order
+pub fn check_range(axis: DisplayAxis, start: u16, end: u16, size: u16) -> Result<(), DisplayError> {
+  let max = size.saturating_sub(1);
+
+  if start > max {
+    return Err(DisplayError::OutOfBounds { axis, value: start, max });
+  }
+
+  if end > max {
+    return Err(DisplayError::OutOfBounds { axis, value: end, max });
+  }
+
+  if start > end {
+    return Err(DisplayError::InvalidRange { axis, start, end });
+  }
+
+  Ok(())
+}
+
+// start_x..=end_x and start_y..=end_y, the two ranges of a window, checked
+// as check_range does on a width x height screen
+pub fn check_window(start_x: u16, start_y: u16, end_x: u16, end_y: u16, width: u16, height: u16) -> Result<(), DisplayError> {
+  check_range(DisplayAxis::X, start_x, end_x, width)?;
+  check_range(DisplayAxis::Y, start_y, end_y, height)
+}
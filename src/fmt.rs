@@ -0,0 +1,137 @@
+// Integer to text without heapless::String: the digits are written into the
+// caller's buffer and the returned &str borrows it. When the buffer is too
+// small nothing is written and an empty string is returned.
+
+// Characters of the longest u32 (4294967295) and i32 (-2147483648)
+pub const U32_MAX_LENGTH: usize = 10;
+#[allow(dead_code)]
+pub const I32_MAX_LENGTH: usize = 11;
+
+pub fn format_u32_into(buf: &mut [u8], value: u32) -> &str {
+  let mut digits = [0u8; U32_MAX_LENGTH];
+  let count = write_digits(&mut digits, value);
+
+  if buf.len() < count {
+    return "";
+  }
+
+  buf[..count].copy_from_slice(&digits[U32_MAX_LENGTH - count..]);
+
+  as_str(&buf[..count])
+}
+
+// Right aligned in a field of at least min_width characters, filled on the
+// left with pad (ASCII, e.g. b' ' or b'0'), so readouts don't shift as the
+// digit count changes: 42 in 4 with b'0' gives "0042"
+pub fn format_u32_padded_into(buf: &mut [u8], value: u32, min_width: usize, pad: u8) -> &str {
+  let mut digits = [0u8; U32_MAX_LENGTH];
+  let count = write_digits(&mut digits, value);
+  let width = count.max(min_width);
+
+  if buf.len() < width || !pad.is_ascii() {
+    return "";
+  }
+
+  buf[..width - count].fill(pad);
+  buf[width - count..width].copy_from_slice(&digits[U32_MAX_LENGTH - count..]);
+
+  as_str(&buf[..width])
+}
+
+#[allow(dead_code)]
+pub fn format_i32_into(buf: &mut [u8], value: i32) -> &str {
+  let mut digits = [0u8; U32_MAX_LENGTH];
+  let count = write_digits(&mut digits, value.unsigned_abs());
+  let sign = (value < 0) as usize;
+
+  if buf.len() < sign + count {
+    return "";
+  }
+
+  if sign == 1 {
+    buf[0] = b'-';
+  }
+
+  buf[sign..sign + count].copy_from_slice(&digits[U32_MAX_LENGTH - count..]);
+
+  as_str(&buf[..sign + count])
+}
+
+// label followed by the value, e.g. "FPS:\n" and 60 give "FPS:\n60"
+pub fn format_labeled_u32_into<'a>(buf: &'a mut [u8], label: &str, value: u32) -> &'a str {
+  let label_length = label.len();
+
+  if buf.len() < label_length {
+    return "";
+  }
+
+  buf[..label_length].copy_from_slice(label.as_bytes());
+
+  let count = format_u32_into(&mut buf[label_length..], value).len();
+
+  if count == 0 {
+    return "";
+  }
+
+  as_str(&buf[..label_length + count])
+}
+
+// Writes the digits right aligned in digits and returns how many there are
+fn write_digits(digits: &mut [u8; U32_MAX_LENGTH], mut value: u32) -> usize {
+  let mut count = 0;
+
+  loop {
+    count += 1;
+
+    digits[U32_MAX_LENGTH - count] = b'0' + (value % 10) as u8;
+
+    value /= 10;
+
+    if value == 0 {
+      return count;
+    }
+  }
+}
+
+fn as_str(bytes: &[u8]) -> &str {
+  core::str::from_utf8(bytes).unwrap_or("")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn formats_boundary_values() {
+    let mut buf = [0u8; U32_MAX_LENGTH];
+
+    assert_eq!(format_u32_into(&mut buf, 0), "0");
+    assert_eq!(format_u32_into(&mut buf, 9), "9");
+    assert_eq!(format_u32_into(&mut buf, 10), "10");
+    assert_eq!(format_u32_into(&mut buf, u32::MAX), "4294967295");
+  }
+
+  #[test]
+  fn too_small_buffer_gives_empty_string() {
+    let mut buf = [0u8; U32_MAX_LENGTH - 1];
+
+    assert_eq!(format_u32_into(&mut buf, u32::MAX), "");
+    assert_eq!(format_u32_into(&mut buf, 999_999_999), "999999999");
+  }
+
+  #[test]
+  fn formats_i32_extremes() {
+    let mut buf = [0u8; I32_MAX_LENGTH];
+
+    assert_eq!(format_i32_into(&mut buf, i32::MIN), "-2147483648");
+    assert_eq!(format_i32_into(&mut buf, i32::MAX), "2147483647");
+  }
+
+  #[test]
+  fn pads_to_min_width() {
+    let mut buf = [0u8; U32_MAX_LENGTH];
+
+    assert_eq!(format_u32_padded_into(&mut buf, 42, 4, b'0'), "0042");
+    assert_eq!(format_u32_padded_into(&mut buf, u32::MAX, 4, b' '), "4294967295");
+  }
+}
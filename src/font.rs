@@ -2816,3 +2816,80 @@ pub const FONT: [[u8; 8]; 256] = [
     0b00000000
   ],
 ];
+
+// The (column, row) of every pixel of the FONT glyph at index in its 8x8 cell,
+// row by row from the top-left corner, and whether it's set. Indexes out of
+// FONT give no pixels
+pub fn glyph_pixels(index: usize) -> impl Iterator<Item = (u16, u16, bool)> {
+  FONT.get(index).into_iter().flat_map(|glyph| {
+    (0..8u16).flat_map(move |row| {
+      (0..8u16).map(move |column| (column, row, glyph[row as usize] & (0x80 >> column) != 0))
+    })
+  })
+}
+
+// Width and height of the FONT glyphs at scale 1
+pub const FONT_CELL_SIZE: u16 = 8;
+
+// Glyph rows above the baseline in FONT (rows 1-6 hold the capitals)
+pub const FONT_BASELINE: u16 = 7;
+
+// Largest scale at which columns x rows glyphs fit in max_width x max_height
+// pixels, 0 when not even scale 1 does; no limit with nothing to fit
+pub fn scale_to_fit(columns: u16, rows: u16, max_width: u16, max_height: u16) -> u16 {
+  let scale_x = match columns {
+    0       => u16::MAX,
+    columns => (max_width as u32 / (columns as u32 * FONT_CELL_SIZE as u32)) as u16
+  };
+
+  let scale_y = match rows {
+    0    => u16::MAX,
+    rows => (max_height as u32 / (rows as u32 * FONT_CELL_SIZE as u32)) as u16
+  };
+
+  scale_x.min(scale_y)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // The top of the 'A' draw_char draws: an empty row, then 0b00111000
+  #[test]
+  fn glyph_pixels_of_a() {
+    let pixels: Vec<(u16, u16, bool)> = glyph_pixels('A' as usize).collect();
+
+    assert_eq!(pixels.len(), 64);
+    assert!(pixels[..8].iter().all(|&(_, row, set)| row == 0 && !set));
+
+    let set: Vec<u16> = pixels[8..16].iter().filter(|&&(_, _, set)| set).map(|&(column, _, _)| column).collect();
+
+    assert_eq!(set, [2, 3, 4]);
+  }
+
+  #[test]
+  fn glyph_pixels_out_of_font() {
+    assert_eq!(glyph_pixels(256).count(), 0);
+  }
+
+  #[test]
+  fn longer_text_gets_a_smaller_scale() {
+    let short = scale_to_fit(4, 1, 240, 80);
+    let long = scale_to_fit(20, 1, 240, 80);
+
+    assert_eq!(short, 7);
+    assert_eq!(long, 1);
+    assert!(long < short);
+  }
+
+  #[test]
+  fn limited_by_the_tighter_axis() {
+    assert_eq!(scale_to_fit(2, 1, 240, 16), 2);
+    assert_eq!(scale_to_fit(2, 3, 240, 240), 10);
+  }
+
+  #[test]
+  fn zero_when_scale_one_does_not_fit() {
+    assert_eq!(scale_to_fit(31, 1, 240, 240), 0);
+  }
+}
@@ -0,0 +1,101 @@
+use crate::display::Display;
+use crate::timing::elapsed;
+use embedded_hal::{
+  blocking::delay::DelayMs,
+  watchdog::{
+    Watchdog as _,
+    WatchdogEnable
+  }
+};
+use fugit::ExtU32;
+use rp2040_hal::{
+  Timer,
+  watchdog::Watchdog
+};
+
+// What the frame complete callback gets about the frame that just ended
+#[derive(Clone, Copy)]
+pub struct FrameContext {
+  pub elapsed_us: u32, // time the frame work took
+  pub frame     : u32  // frames run so far, this one included
+}
+
+// Called by FrameGuard::run after every frame, e.g. to blink a LED or log
+// profiling data; it runs after the stall check (and a reinit if any)
+pub type FrameCallback = fn(&FrameContext);
+
+// Runs the per-frame work feeding the watchdog around it. Two limits apply:
+// - watchdog_timeout_us: a frame that hangs for longer resets the whole chip.
+//   The RP2040 counter caps it at 8_388_607us (~8.3s), more panics at start
+// - max_frame_time_us: a frame that finishes but took longer is treated as a
+//   stall of the panel (e.g. a glitch on the SPI lines) and the display is
+//   reinitialized. Keep it well above the slowest normal frame
+// The watchdog timeout must be longer than max_frame_time_us plus the ~400ms
+// a reinit takes, or the recovery itself trips it.
+pub struct FrameGuard {
+  frames        : u32,
+  max_frame_time: u32,
+  on_complete   : Option<FrameCallback>,
+  stalls        : u32,
+  watchdog      : Watchdog
+}
+
+#[allow(dead_code)]
+impl FrameGuard {
+  pub fn new(mut watchdog: Watchdog, watchdog_timeout_us: u32, max_frame_time_us: u32) -> Self {
+    watchdog.start(watchdog_timeout_us.micros());
+
+    Self {
+      frames        : 0,
+      max_frame_time: max_frame_time_us,
+      on_complete   : None,
+      stalls        : 0,
+      watchdog
+    }
+  }
+
+  // None removes it
+  pub fn on_frame_complete(&mut self, callback: Option<FrameCallback>) {
+    self.on_complete = callback;
+  }
+
+  pub fn frames(&self) -> u32 {
+    self.frames
+  }
+
+  // Frames that exceeded max_frame_time_us since start
+  pub fn stalls(&self) -> u32 {
+    self.stalls
+  }
+
+  pub fn run<const BUFFER_SIZE: usize, R, F: FnOnce(&mut Display<BUFFER_SIZE>) -> R>(&mut self, display: &mut Display<BUFFER_SIZE>, timer: &Timer, delay: &mut impl DelayMs<u32>, frame: F) -> R {
+    self.watchdog.feed();
+
+    let time_start = timer.get_counter_low();
+    let result = frame(display);
+    let time_diff = elapsed(timer.get_counter_low(), time_start);
+
+    if time_diff > self.max_frame_time {
+      self.stalls += 1;
+
+      defmt::warn!("Frame took {=u32}us, reinitializing the display", time_diff);
+
+      self.watchdog.feed();
+
+      display.reinit(delay);
+    }
+
+    self.watchdog.feed();
+
+    self.frames = self.frames.wrapping_add(1);
+
+    if let Some(callback) = self.on_complete {
+      callback(&FrameContext {
+        elapsed_us: time_diff,
+        frame     : self.frames
+      });
+    }
+
+    result
+  }
+}
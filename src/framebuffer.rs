@@ -0,0 +1,308 @@
+use crate::geometry::Rect;
+
+// Solid rects, fills, pixels and lines on either the panel or an off-screen
+// FrameBuffer (Display implements it in display.rs). text::draw_glyph draws
+// text on any DrawTarget too; gradients, pies and tiles are Display only.
+// Colors are in the format of the display color mode, as everywhere else
+#[allow(dead_code)]
+pub trait DrawTarget {
+  fn size(&self) -> (u16, u16);
+
+  // Clipped to the target, empty rects draw nothing
+  fn draw_solid_rect(&mut self, x: u16, y: u16, width: u16, height: u16, color: u32);
+
+  fn fill(&mut self, color: u32) {
+    let (width, height) = self.size();
+
+    self.draw_solid_rect(0, 0, width, height, color);
+  }
+
+  fn fill_rect(&mut self, rect: Rect, color: u32) {
+    self.draw_solid_rect(rect.x, rect.y, rect.width, rect.height, color);
+  }
+
+  fn set_pixel(&mut self, x: u16, y: u16, color: u32) {
+    self.draw_solid_rect(x, y, 1, 1, color);
+  }
+
+  // Both end points are drawn. Horizontal and vertical lines (grids, borders)
+  // go out as a single 1 pixel thick rect, the rest pixel by pixel
+  fn draw_line(&mut self, x0: u16, y0: u16, x1: u16, y1: u16, color: u32) {
+    if y0 == y1 {
+      self.draw_solid_rect(x0.min(x1), y0, x0.abs_diff(x1).saturating_add(1), 1, color);
+
+      return;
+    }
+
+    if x0 == x1 {
+      self.draw_solid_rect(x0, y0.min(y1), 1, y0.abs_diff(y1).saturating_add(1), color);
+
+      return;
+    }
+
+    let (mut x, mut y) = (x0 as i32, y0 as i32);
+    let (x1, y1) = (x1 as i32, y1 as i32);
+
+    let dx = (x1 - x).abs();
+    let dy = -(y1 - y).abs();
+    let step_x = match x < x1 {
+      true  => 1,
+      false => -1
+    };
+    let step_y = match y < y1 {
+      true  => 1,
+      false => -1
+    };
+
+    let mut error = dx + dy;
+
+    loop {
+      self.set_pixel(x as u16, y as u16, color);
+
+      if x == x1 && y == y1 {
+        break;
+      }
+
+      let error2 = error * 2;
+
+      if error2 >= dy {
+        error += dy;
+        x += step_x;
+      }
+
+      if error2 <= dx {
+        error += dx;
+        y += step_y;
+      }
+    }
+  }
+}
+
+// Off-screen W x H canvas sized to what needs buffering (a sprite canvas, a
+// status bar) instead of the whole screen. Pixels are stored packed as the
+// panel takes them, BYTES_PER_PIXEL each: 2 for BPP16 (the default, half the
+// RAM of one u32 per pixel) or 3 for BPP16M/BPP18. BPP12 packs pixel pairs
+// and isn't supported. Draw it with
+// display.draw_image(x, y, W as u16, H as u16, framebuffer.as_bytes())
+// while the display is in the matching color mode
+pub struct FrameBuffer<const W: usize, const H: usize, const BYTES_PER_PIXEL: usize = 2> {
+  pixels: [[[u8; BYTES_PER_PIXEL]; W]; H]
+}
+
+impl<const W: usize, const H: usize, const BYTES_PER_PIXEL: usize> Default for FrameBuffer<W, H, BYTES_PER_PIXEL> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+#[allow(dead_code)]
+impl<const W: usize, const H: usize, const BYTES_PER_PIXEL: usize> FrameBuffer<W, H, BYTES_PER_PIXEL> {
+  pub const fn new() -> Self {
+    // draw_solid_rect takes the pixel bytes from a u32 color
+    const { assert!(BYTES_PER_PIXEL <= 4, "BYTES_PER_PIXEL must be at most 4") };
+
+    Self {
+      pixels: [[[0; BYTES_PER_PIXEL]; W]; H]
+    }
+  }
+
+  pub fn as_bytes(&self) -> &[u8] {
+    self.pixels.as_flattened().as_flattened()
+  }
+
+  // Out of the canvas it's 0
+  pub fn pixel(&self, x: u16, y: u16) -> u32 {
+    match self.pixels.get(y as usize).and_then(|row| row.get(x as usize)) {
+      Some(bytes) => bytes.iter().fold(0, |color, &byte| color << 8 | byte as u32),
+      None        => 0
+    }
+  }
+
+  // Out of the canvas it's empty
+  pub fn row(&self, y: u16) -> &[u8] {
+    match self.pixels.get(y as usize) {
+      Some(row) => row.as_flattened(),
+      None      => &[]
+    }
+  }
+}
+
+impl<const W: usize, const H: usize, const BYTES_PER_PIXEL: usize> DrawTarget for FrameBuffer<W, H, BYTES_PER_PIXEL> {
+  fn size(&self) -> (u16, u16) {
+    (W as u16, H as u16)
+  }
+
+  fn draw_solid_rect(&mut self, x: u16, y: u16, width: u16, height: u16, color: u32) {
+    let end_x = (x as usize + width as usize).min(W);
+    let end_y = (y as usize + height as usize).min(H);
+
+    // The low bytes of the color, most significant first
+    let bytes = color.to_be_bytes();
+    let bytes = &bytes[4 - BYTES_PER_PIXEL..];
+
+    for row in self.pixels.iter_mut().take(end_y).skip(y as usize) {
+      for pixel in row.iter_mut().take(end_x).skip(x as usize) {
+        pixel.copy_from_slice(bytes);
+      }
+    }
+  }
+}
+
+// A whole screen of pixels in RAM, the framebuffer of Display. The pixel
+// data sent to the window (start_x, start_y, end_x, end_y) lands in it like in
+// the panel memory, wrapping to the window start when it is full. The screen
+// width and color mode can change with rotation and set_bpp, so they are
+// passed to each call
+pub struct ScreenBuffer<'a> {
+  pub data: &'a mut [u8],
+  offset  : usize, // bytes of the window already written
+  window  : (u16, u16, u16, u16)
+}
+
+#[allow(dead_code)]
+impl<'a> ScreenBuffer<'a> {
+  pub fn new(data: &'a mut [u8], width: u16, height: u16) -> Self {
+    Self {
+      data,
+      offset: 0,
+      window: (0, 0, width.saturating_sub(1), height.saturating_sub(1))
+    }
+  }
+
+  pub fn set_window(&mut self, start_x: u16, start_y: u16, end_x: u16, end_y: u16) {
+    self.offset = 0;
+    self.window = (start_x, start_y, end_x, end_y);
+  }
+
+  pub fn write(&mut self, mut data: &[u8], screen_width: u16, bytes_per_pixel: usize) {
+    let (start_x, start_y, end_x, end_y) = self.window;
+    let row_bytes = (end_x - start_x + 1) as usize * bytes_per_pixel;
+    let window_bytes = (end_y - start_y + 1) as usize * row_bytes;
+
+    if window_bytes == 0 {
+      return;
+    }
+
+    while !data.is_empty() {
+      let row = self.offset / row_bytes;
+      let column = self.offset % row_bytes;
+      let length = (row_bytes - column).min(data.len());
+      let start = ((start_y as usize + row) * screen_width as usize + start_x as usize) * bytes_per_pixel + column;
+
+      if let Some(destination) = self.data.get_mut(start..start + length) {
+        destination.copy_from_slice(&data[..length]);
+      }
+
+      data = &data[length..];
+      self.offset = (self.offset + length) % window_bytes;
+    }
+  }
+
+  // Copies the pixels of region into dst row by row; dst must hold them all
+  pub fn copy_region(&self, region: Rect, screen_width: u16, bytes_per_pixel: usize, dst: &mut [u8]) {
+    let row_bytes = region.width as usize * bytes_per_pixel;
+
+    for row in 0..region.height as usize {
+      let start = ((region.y as usize + row) * screen_width as usize + region.x as usize) * bytes_per_pixel;
+
+      if let Some(pixels) = self.data.get(start..start + row_bytes) {
+        dst[row * row_bytes..(row + 1) * row_bytes].copy_from_slice(pixels);
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Keeps the rects drawn on it
+  struct Recorder {
+    rects: Vec<(u16, u16, u16, u16)>
+  }
+
+  impl DrawTarget for Recorder {
+    fn size(&self) -> (u16, u16) {
+      (320, 240)
+    }
+
+    fn draw_solid_rect(&mut self, x: u16, y: u16, width: u16, height: u16, _color: u32) {
+      self.rects.push((x, y, width, height));
+    }
+  }
+
+  #[test]
+  fn rect_bytes_in_a_small_framebuffer() {
+    let mut framebuffer: FrameBuffer<4, 3> = FrameBuffer::new();
+
+    framebuffer.fill_rect(Rect::new(1, 1, 2, 5), 0xF81F);
+
+    assert_eq!(framebuffer.as_bytes(), &[
+      0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+      0x00, 0x00, 0xF8, 0x1F, 0xF8, 0x1F, 0x00, 0x00,
+      0x00, 0x00, 0xF8, 0x1F, 0xF8, 0x1F, 0x00, 0x00
+    ]);
+    assert_eq!(framebuffer.pixel(2, 2), 0xF81F);
+    assert_eq!(framebuffer.row(1), &[0x00, 0x00, 0xF8, 0x1F, 0xF8, 0x1F, 0x00, 0x00]);
+  }
+
+  #[test]
+  fn out_of_the_canvas_is_empty() {
+    let mut framebuffer: FrameBuffer<2, 2, 3> = FrameBuffer::new();
+
+    framebuffer.fill(0x123456);
+
+    assert_eq!(framebuffer.pixel(1, 1), 0x123456);
+    assert_eq!(framebuffer.pixel(2, 0), 0);
+    assert_eq!(framebuffer.pixel(0, 2), 0);
+    assert_eq!(framebuffer.row(1), &[0x12, 0x34, 0x56, 0x12, 0x34, 0x56]);
+    assert!(framebuffer.row(2).is_empty());
+  }
+
+  #[test]
+  fn straight_lines_are_one_rect() {
+    let mut recorder = Recorder { rects: Vec::new() };
+
+    recorder.draw_line(30, 5, 10, 5, 0xFFFF);
+    recorder.draw_line(7, 2, 7, 4, 0xFFFF);
+
+    assert_eq!(recorder.rects, [(10, 5, 21, 1), (7, 2, 1, 3)]);
+
+    recorder.rects.clear();
+    recorder.draw_line(0, 0, 2, 2, 0xFFFF);
+
+    assert_eq!(recorder.rects, [(0, 0, 1, 1), (1, 1, 1, 1), (2, 2, 1, 1)]);
+  }
+
+  // What send_data does to a 2x2 window at (1, 1) of a 4x3 BPP16 screen, and
+  // what copy_region_to reads back from it
+  #[test]
+  fn window_writes_read_back_the_same() {
+    let mut data = [0u8; 24];
+    let mut screen = ScreenBuffer::new(&mut data, 4, 3);
+    let pixels = [1, 2, 3, 4, 5, 6, 7, 8];
+    let mut copy = [0u8; 8];
+
+    screen.set_window(1, 1, 2, 2);
+    screen.write(&pixels[..3], 4, 2);
+    screen.write(&pixels[3..], 4, 2);
+    screen.copy_region(Rect::new(1, 1, 2, 2), 4, 2, &mut copy);
+
+    assert_eq!(copy, pixels);
+    assert_eq!(screen.data, &[
+      0, 0, 0, 0, 0, 0, 0, 0,
+      0, 0, 1, 2, 3, 4, 0, 0,
+      0, 0, 5, 6, 7, 8, 0, 0
+    ]);
+  }
+
+  #[test]
+  fn full_window_wraps_to_its_start() {
+    let mut data = [0u8; 4];
+    let mut screen = ScreenBuffer::new(&mut data, 2, 2);
+
+    screen.write(&[1, 2, 3, 4, 5, 6], 2, 1);
+
+    assert_eq!(screen.data, &[5, 6, 3, 4]);
+  }
+}
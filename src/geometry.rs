@@ -0,0 +1,324 @@
+use crate::error::{
+  check_range,
+  DisplayAxis,
+  DisplayError
+};
+
+// Screen pixel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Point {
+  pub x: u16,
+  pub y: u16
+}
+
+// Width and height in pixels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Size {
+  pub height: u16,
+  pub width : u16
+}
+
+#[allow(dead_code)]
+impl Point {
+  pub fn new(x: u16, y: u16) -> Self {
+    Self {
+      x,
+      y
+    }
+  }
+}
+
+#[allow(dead_code)]
+impl Size {
+  pub fn new(width: u16, height: u16) -> Self {
+    Self {
+      height,
+      width
+    }
+  }
+}
+
+// Screen rectangle, (x, y) being its top-left pixel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+  pub height: u16,
+  pub width : u16,
+  pub x     : u16,
+  pub y     : u16
+}
+
+#[allow(dead_code)]
+impl Rect {
+  pub fn new(x: u16, y: u16, width: u16, height: u16) -> Self {
+    Self {
+      height,
+      width,
+      x,
+      y
+    }
+  }
+
+  pub fn from_point_size(origin: Point, size: Size) -> Self {
+    Self::new(origin.x, origin.y, size.width, size.height)
+  }
+
+  // From the first and the last pixel, both included, as set_window takes them
+  pub fn from_corners(start: Point, end: Point) -> Self {
+    let start_x = start.x.min(end.x);
+    let start_y = start.y.min(end.y);
+
+    Self::new(start_x, start_y, start.x.max(end.x) - start_x + 1, start.y.max(end.y) - start_y + 1)
+  }
+
+  pub fn origin(&self) -> Point {
+    Point::new(self.x, self.y)
+  }
+
+  pub fn size(&self) -> Size {
+    Size::new(self.width, self.height)
+  }
+
+  // Last pixel (bottom-right, included), None for an empty rect or one that
+  // runs past the u16 coordinates
+  pub fn end(&self) -> Option<Point> {
+    if self.is_empty() {
+      return None;
+    }
+
+    Some(Point::new(self.x.checked_add(self.width - 1)?, self.y.checked_add(self.height - 1)?))
+  }
+
+  pub fn contains(&self, point: Point) -> bool {
+    point.x >= self.x &&
+    point.y >= self.y &&
+    (point.x as u32) < self.x as u32 + self.width as u32 &&
+    (point.y as u32) < self.y as u32 + self.height as u32
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.width == 0 || self.height == 0
+  }
+
+  // Overlapping part of both, None when they don't overlap
+  pub fn intersection(&self, other: &Rect) -> Option<Rect> {
+    let start_x = self.x.max(other.x);
+    let start_y = self.y.max(other.y);
+    let end_x = (self.x as u32 + self.width as u32).min(other.x as u32 + other.width as u32);
+    let end_y = (self.y as u32 + self.height as u32).min(other.y as u32 + other.height as u32);
+
+    if end_x <= start_x as u32 || end_y <= start_y as u32 {
+      return None;
+    }
+
+    Some(Rect::new(start_x, start_y, (end_x - start_x as u32) as u16, (end_y - start_y as u32) as u16))
+  }
+
+  // First and last pixel of the part inside clip, as set_window takes them;
+  // None for an empty rect (width or height 0) or one outside the clip
+  pub fn clipped_window(&self, clip: &Rect) -> Option<(Point, Point)> {
+    let clipped = clip.intersection(self)?;
+
+    Some((clipped.origin(), clipped.end()?))
+  }
+
+  // The union of both when they are side by side (same rows, touching columns)
+  // or stacked (same columns, touching rows) and so form a single rectangle,
+  // None too when that rectangle would be wider or taller than a u16
+  pub fn merge_adjacent(&self, other: &Rect) -> Option<Rect> {
+    if self.y == other.y && self.height == other.height {
+      if self.x as u32 + self.width as u32 == other.x as u32 {
+        return Some(Rect::new(self.x, self.y, self.width.checked_add(other.width)?, self.height));
+      }
+
+      if other.x as u32 + other.width as u32 == self.x as u32 {
+        return Some(Rect::new(other.x, self.y, self.width.checked_add(other.width)?, self.height));
+      }
+    }
+
+    if self.x == other.x && self.width == other.width {
+      if self.y as u32 + self.height as u32 == other.y as u32 {
+        return Some(Rect::new(self.x, self.y, self.width, self.height.checked_add(other.height)?));
+      }
+
+      if other.y as u32 + other.height as u32 == self.y as u32 {
+        return Some(Rect::new(self.x, other.y, self.width, self.height.checked_add(other.height)?));
+      }
+    }
+
+    None
+  }
+}
+
+// Moves the rect inside a width x height area, shrinking it first if it is
+// bigger, so dragging or resizing against an edge stops flat at it instead of
+// bouncing between two positions
+pub fn clamp_rect_to_bounds(rect: Rect, width: u16, height: u16) -> Rect {
+  let clamped_width = rect.width.min(width);
+  let clamped_height = rect.height.min(height);
+
+  Rect::new(
+    rect.x.min(width - clamped_width),
+    rect.y.min(height - clamped_height),
+    clamped_width,
+    clamped_height
+  )
+}
+
+// Bar number bar of bars side by side across rect, as tall as value is of max
+// (values above max are cut to it) and standing on the bottom of rect. The
+// sums saturate, so a rect at the edge of the u16 coordinates can't wrap
+pub fn bar_chart_bar(rect: Rect, bars: usize, bar: usize, value: u16, max: u16) -> Rect {
+  if bars == 0 || max == 0 {
+    return Rect::new(rect.x, rect.y, 0, 0);
+  }
+
+  let bar_width = rect.width / bars.min(u16::MAX as usize) as u16;
+  let bar_height = (value.min(max) as u32 * rect.height as u32 / max as u32) as u16;
+
+  Rect::new(
+    rect.x.saturating_add((bar as u16).saturating_mul(bar_width)),
+    rect.y.saturating_add(rect.height - bar_height),
+    bar_width,
+    bar_height
+  )
+}
+
+// The rows start_row..=end_row across the whole width x height screen, what
+// fill_rows clears in a single window
+pub fn row_band(start_row: u16, end_row: u16, width: u16, height: u16) -> Result<Rect, DisplayError> {
+  check_range(DisplayAxis::Y, start_row, end_row, height)?;
+
+  Ok(Rect::new(0, start_row, width, end_row - start_row + 1))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn merges_side_by_side_rects() {
+    let left = Rect::new(10, 20, 30, 5);
+    let right = Rect::new(40, 20, 15, 5);
+
+    assert_eq!(left.merge_adjacent(&right), Some(Rect::new(10, 20, 45, 5)));
+    assert_eq!(right.merge_adjacent(&left), Some(Rect::new(10, 20, 45, 5)));
+  }
+
+  #[test]
+  fn merges_stacked_rects() {
+    let top = Rect::new(0, 0, 8, 8);
+    let bottom = Rect::new(0, 8, 8, 4);
+
+    assert_eq!(top.merge_adjacent(&bottom), Some(Rect::new(0, 0, 8, 12)));
+  }
+
+  #[test]
+  fn does_not_merge_gaps_or_different_heights() {
+    let rect = Rect::new(0, 0, 10, 10);
+
+    assert_eq!(rect.merge_adjacent(&Rect::new(11, 0, 10, 10)), None);
+    assert_eq!(rect.merge_adjacent(&Rect::new(10, 0, 10, 9)), None);
+  }
+
+  #[test]
+  fn does_not_merge_past_the_u16_size() {
+    let left = Rect::new(0, 0, 40000, 1);
+    let right = Rect::new(40000, 0, 30000, 1);
+    let top = Rect::new(0, 0, 1, 40000);
+    let bottom = Rect::new(0, 40000, 1, 30000);
+
+    assert_eq!(left.merge_adjacent(&right), None);
+    assert_eq!(right.merge_adjacent(&left), None);
+    assert_eq!(top.merge_adjacent(&bottom), None);
+    assert_eq!(bottom.merge_adjacent(&top), None);
+  }
+
+  #[test]
+  fn end_past_the_coordinates_is_none() {
+    assert_eq!(Rect::new(10, 20, 5, 5).end(), Some(Point::new(14, 24)));
+    assert_eq!(Rect::new(65535, 0, 1, 1).end(), Some(Point::new(65535, 0)));
+    assert_eq!(Rect::new(65535, 0, 2, 1).end(), None);
+    assert_eq!(Rect::new(0, 65535, 1, 2).end(), None);
+  }
+
+  #[test]
+  fn clamps_against_each_edge() {
+    assert_eq!(clamp_rect_to_bounds(Rect::new(230, 50, 20, 10), 240, 135), Rect::new(220, 50, 20, 10));
+    assert_eq!(clamp_rect_to_bounds(Rect::new(50, 130, 20, 10), 240, 135), Rect::new(50, 125, 20, 10));
+    assert_eq!(clamp_rect_to_bounds(Rect::new(0, 50, 20, 10), 240, 135), Rect::new(0, 50, 20, 10));
+    assert_eq!(clamp_rect_to_bounds(Rect::new(50, 0, 20, 10), 240, 135), Rect::new(50, 0, 20, 10));
+    assert_eq!(clamp_rect_to_bounds(Rect::new(239, 134, 20, 10), 240, 135), Rect::new(220, 125, 20, 10));
+  }
+
+  // Pushing further against an edge keeps the same rect, no bouncing
+  #[test]
+  fn stays_put_when_pushed_past_an_edge() {
+    let first = clamp_rect_to_bounds(Rect::new(225, 0, 20, 10), 240, 135);
+    let second = clamp_rect_to_bounds(Rect::new(first.x + 1, 0, 20, 10), 240, 135);
+
+    assert_eq!(first, second);
+  }
+
+  #[test]
+  fn rect_bigger_than_the_bounds_is_shrunk() {
+    assert_eq!(clamp_rect_to_bounds(Rect::new(10, 10, 300, 200), 240, 135), Rect::new(0, 0, 240, 135));
+    assert_eq!(clamp_rect_to_bounds(Rect::new(100, 20, 300, 10), 240, 135), Rect::new(0, 20, 240, 10));
+  }
+
+  // What push_clip does for each nested clip
+  #[test]
+  fn nested_intersections_narrow_down() {
+    let screen = Rect::new(0, 0, 240, 240);
+    let outer = screen.intersection(&Rect::new(20, 20, 100, 100)).unwrap();
+    let inner = outer.intersection(&Rect::new(100, 0, 100, 50)).unwrap();
+
+    assert_eq!(outer, Rect::new(20, 20, 100, 100));
+    assert_eq!(inner, Rect::new(100, 20, 20, 30));
+    assert_eq!(inner.intersection(&Rect::new(0, 200, 10, 10)), None);
+  }
+
+  // draw_bar_chart with 4 bars over a 40x20 rect at (10, 100) and max 100
+  #[test]
+  fn bars_stand_on_the_bottom_of_the_chart() {
+    let chart = Rect::new(10, 100, 40, 20);
+
+    assert_eq!(bar_chart_bar(chart, 4, 0, 0, 100), Rect::new(10, 120, 10, 0));
+    assert_eq!(bar_chart_bar(chart, 4, 1, 50, 100), Rect::new(20, 110, 10, 10));
+    assert_eq!(bar_chart_bar(chart, 4, 3, 100, 100), Rect::new(40, 100, 10, 20));
+    assert_eq!(bar_chart_bar(chart, 4, 2, 250, 100), Rect::new(30, 100, 10, 20));
+  }
+
+  #[test]
+  fn bars_at_the_end_of_the_coordinates_saturate() {
+    let chart = Rect::new(u16::MAX - 5, u16::MAX - 2, 40, 20);
+
+    assert_eq!(bar_chart_bar(chart, 4, 3, 5, 10), Rect::new(u16::MAX, u16::MAX, 10, 10));
+  }
+
+  // draw_solid_rect must open no window for these
+  #[test]
+  fn empty_rects_have_no_window() {
+    let screen = Rect::new(0, 0, 240, 240);
+
+    assert_eq!(Rect::new(10, 10, 0, 5).clipped_window(&screen), None);
+    assert_eq!(Rect::new(10, 10, 5, 0).clipped_window(&screen), None);
+    assert_eq!(Rect::new(0, 0, 0, 0).clipped_window(&screen), None);
+    assert_eq!(Rect::new(240, 10, 5, 5).clipped_window(&screen), None);
+  }
+
+  #[test]
+  fn window_corners_are_cut_at_the_clip() {
+    let screen = Rect::new(0, 0, 240, 240);
+
+    assert_eq!(Rect::new(10, 20, 1, 1).clipped_window(&screen), Some((Point::new(10, 20), Point::new(10, 20))));
+    assert_eq!(Rect::new(230, 5, 20, 10).clipped_window(&screen), Some((Point::new(230, 5), Point::new(239, 14))));
+  }
+
+  #[test]
+  fn row_band_must_be_on_the_screen_and_in_order() {
+    assert_eq!(row_band(10, 19, 240, 320), Ok(Rect::new(0, 10, 240, 10)));
+    assert_eq!(row_band(319, 319, 240, 320), Ok(Rect::new(0, 319, 240, 1)));
+    assert_eq!(row_band(300, 320, 240, 320), Err(DisplayError::OutOfBounds { axis: DisplayAxis::Y, value: 320, max: 319 }));
+    assert_eq!(row_band(20, 10, 240, 320), Err(DisplayError::InvalidRange { axis: DisplayAxis::Y, start: 20, end: 10 }));
+  }
+}
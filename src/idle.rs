@@ -0,0 +1,114 @@
+use crate::buttons::{
+  ButtonSet,
+  JoystickStates
+};
+use crate::timing::elapsed;
+
+// Dims the display after a period without joystick input and restores the
+// previous brightness on the next press. The timeout is in ticks (see timing)
+pub struct IdleDimmer {
+  brightness       : u8,
+  dimmed           : bool,
+  dimmed_brightness: u8,
+  ignored          : ButtonSet,
+  last_input       : u32,
+  timeout          : u32
+}
+
+#[allow(dead_code)]
+impl IdleDimmer {
+  pub fn new(timeout: u32, dimmed_brightness: u8, now: u32) -> Self {
+    Self {
+      brightness       : 0xFF,
+      dimmed           : false,
+      dimmed_brightness,
+      ignored          : ButtonSet::EMPTY,
+      last_input       : now,
+      timeout
+    }
+  }
+
+  // Buttons whose activity isn't input, e.g. joystick.stuck_buttons()
+  pub fn ignore(&mut self, buttons: ButtonSet) {
+    self.ignored = buttons;
+  }
+
+  pub fn is_dimmed(&self) -> bool {
+    self.dimmed
+  }
+
+  // With joystick.states() and display.brightness(); returns the brightness
+  // to pass to display.set_brightness() when it has to change
+  pub fn update(&mut self, states: &JoystickStates, brightness: u8, now: u32) -> Option<u8> {
+    let watched = ButtonSet::ALL.without(self.ignored);
+
+    if states.is_any_active(Some(watched)) {
+      self.last_input = now;
+
+      if self.dimmed {
+        self.dimmed = false;
+
+        return Some(self.brightness);
+      }
+    }
+    else if !self.dimmed && elapsed(now, self.last_input) >= self.timeout {
+      self.dimmed = true;
+      self.brightness = brightness;
+
+      return Some(self.dimmed_brightness);
+    }
+
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::buttons::JoystickButton;
+
+  #[test]
+  fn dims_after_the_timeout_and_restores_on_a_press() {
+    let mut dimmer = IdleDimmer::new(100, 0x10, 0);
+    let mut states = JoystickStates::from_raw_state(0, 0);
+
+    assert_eq!(dimmer.update(&states, 0xC0, 99), None);
+    assert_eq!(dimmer.update(&states, 0xC0, 100), Some(0x10));
+    assert!(dimmer.is_dimmed());
+    assert_eq!(dimmer.update(&states, 0x10, 500), None);
+
+    states.advance(JoystickButton::A as u16);
+
+    assert_eq!(dimmer.update(&states, 0x10, 501), Some(0xC0));
+    assert!(!dimmer.is_dimmed());
+
+    // Held, it's still input
+    states.advance(JoystickButton::A as u16);
+
+    assert_eq!(dimmer.update(&states, 0xC0, 700), None);
+  }
+
+  #[test]
+  fn ignored_buttons_are_not_input() {
+    let mut dimmer = IdleDimmer::new(100, 0x10, 0);
+    let mut states = JoystickStates::from_raw_state(0, 0);
+
+    dimmer.ignore(ButtonSet::from_raw(JoystickButton::B as u16));
+
+    // Neither the press of the ignored button nor holding it is input
+    states.advance(JoystickButton::B as u16);
+
+    assert_eq!(dimmer.update(&states, 0xFF, 100), Some(0x10));
+
+    states.advance(JoystickButton::B as u16);
+
+    assert_eq!(dimmer.update(&states, 0x10, 150), None);
+
+    // A flapping ignored button doesn't undim the screen either
+    states.advance(0);
+    states.advance(JoystickButton::B as u16);
+
+    assert_eq!(dimmer.update(&states, 0x10, 200), None);
+    assert!(dimmer.is_dimmed());
+  }
+}
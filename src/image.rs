@@ -0,0 +1,184 @@
+use crate::error::DisplayError;
+
+// Pixel data handling behind the Display image methods
+
+// The runs of pixels that differ between two rows, as (first, last) pixel
+// pairs, both included. Runs at most max_gap unchanged pixels apart are
+// joined, as resending those costs less than opening another window
+pub fn changed_runs<'a>(old_row: &'a [u8], new_row: &'a [u8], bytes_per_pixel: usize, max_gap: usize) -> impl Iterator<Item = (usize, usize)> + 'a {
+  let width = new_row.len() / bytes_per_pixel.max(1);
+  let changed = move |pixel: usize| old_row[pixel * bytes_per_pixel..(pixel + 1) * bytes_per_pixel] != new_row[pixel * bytes_per_pixel..(pixel + 1) * bytes_per_pixel];
+
+  let mut pixel = 0;
+
+  core::iter::from_fn(move || {
+    while pixel < width && !changed(pixel) {
+      pixel += 1;
+    }
+
+    if pixel >= width {
+      return None;
+    }
+
+    let start = pixel;
+    let mut end = pixel;
+
+    pixel += 1;
+
+    while pixel < width && pixel - end <= max_gap + 1 {
+      if changed(pixel) {
+        end = pixel;
+      }
+
+      pixel += 1;
+    }
+
+    pixel = end + 1;
+
+    Some((start, end))
+  })
+}
+
+// Bytes per row of a 1 bit per pixel bitmap, padded to a whole byte
+pub fn bitmap_row_bytes(width: u16) -> usize {
+  (width as usize).div_ceil(8)
+}
+
+// Whether the pixel at (column, row) of a width pixels wide 1 bit per pixel
+// bitmap is set, bit 7 of each byte being the leftmost pixel
+pub fn bitmap_bit(bits: &[u8], width: u16, column: u16, row: u16) -> bool {
+  bits[row as usize * bitmap_row_bytes(width) + column as usize / 8] & (0x80 >> (column % 8)) != 0
+}
+
+// Calls color(x, y) for every pixel of the width x height region at (x, y) in
+// row order, handing the colors to send a chunk.len() at a time (the last
+// chunk holding what is left)
+pub fn scan_region<C: FnMut(u16, u16) -> u32, S: FnMut(&[u32])>(x: u16, y: u16, width: u16, height: u16, chunk: &mut [u32], mut color: C, mut send: S) {
+  let mut count = 0usize;
+
+  for pixel_y in y..y.saturating_add(height) {
+    for pixel_x in x..x.saturating_add(width) {
+      chunk[count] = color(pixel_x, pixel_y);
+
+      count += 1;
+
+      if count == chunk.len() {
+        send(chunk);
+
+        count = 0;
+      }
+    }
+  }
+
+  if count > 0 {
+    send(&chunk[..count]);
+  }
+}
+
+// Hands send the first height rows, each of which must be row_bytes long; a
+// row of another length stops there with LengthMismatch, the rows before it
+// already sent
+pub fn stream_rows<I: Iterator<Item = R>, R: AsRef<[u8]>, S: FnMut(&[u8])>(rows: I, row_bytes: usize, height: u16, mut send: S) -> Result<(), DisplayError> {
+  for row in rows.take(height as usize) {
+    let row = row.as_ref();
+
+    if row.len() != row_bytes {
+      return Err(DisplayError::LengthMismatch { expected: row_bytes, length: row.len() });
+    }
+
+    send(row);
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn runs(old_row: &[u8], new_row: &[u8], bytes_per_pixel: usize, max_gap: usize) -> Vec<(usize, usize)> {
+    changed_runs(old_row, new_row, bytes_per_pixel, max_gap).collect()
+  }
+
+  #[test]
+  fn one_changed_pixel_is_one_run() {
+    let old_row = [0u8; 16];
+    let mut new_row = old_row;
+
+    new_row[7] = 0x01; // second byte of pixel 3 in BPP16
+
+    assert_eq!(runs(&old_row, &new_row, 2, 5), [(3, 3)]);
+  }
+
+  #[test]
+  fn identical_rows_have_no_runs() {
+    assert_eq!(runs(&[0x12; 12], &[0x12; 12], 3, 3), []);
+  }
+
+  // Pixels 0 and 3 are 2 unchanged pixels apart, 3 and 9 are 5 apart
+  #[test]
+  fn close_runs_are_joined_and_far_ones_are_not() {
+    let old_row = [0u8; 10];
+    let mut new_row = old_row;
+
+    new_row[0] = 1;
+    new_row[3] = 1;
+    new_row[9] = 1;
+
+    assert_eq!(runs(&old_row, &new_row, 1, 2), [(0, 3), (9, 9)]);
+    assert_eq!(runs(&old_row, &new_row, 1, 5), [(0, 9)]);
+  }
+
+  // A 10 pixel wide bitmap takes 2 bytes a row, the last 6 bits unused
+  #[test]
+  fn bitmap_rows_are_padded_to_whole_bytes() {
+    let bits = [
+      0b1000_0000, 0b0100_0000,
+      0b0000_0001, 0b1011_1111
+    ];
+
+    assert_eq!(bitmap_row_bytes(10), 2);
+    assert_eq!(bitmap_row_bytes(16), 2);
+    assert_eq!(bitmap_row_bytes(0), 0);
+
+    let rows: Vec<Vec<bool>> = (0..2).map(|row| (0..10).map(|column| bitmap_bit(&bits, 10, column, row)).collect()).collect();
+
+    assert_eq!(rows[0], [true, false, false, false, false, false, false, false, false, true]);
+    assert_eq!(rows[1], [false, false, false, false, false, false, false, true, true, false]);
+  }
+
+  // What fill_with streams for a 3x2 region at (10, 20) with 4 color chunks
+  #[test]
+  fn scan_calls_back_in_row_order() {
+    let mut calls = Vec::new();
+    let mut sent = Vec::new();
+
+    scan_region(10, 20, 3, 2, &mut [0; 4], |x, y| {
+      calls.push((x, y));
+
+      (x as u32) << 8 | y as u32
+    }, |colors| sent.push(colors.to_vec()));
+
+    assert_eq!(calls, [(10, 20), (11, 20), (12, 20), (10, 21), (11, 21), (12, 21)]);
+    assert_eq!(sent, [vec![0x0A14, 0x0B14, 0x0C14, 0x0A15], vec![0x0B15, 0x0C15]]);
+  }
+
+  // A 2 pixel wide BPP16 screen 3 rows tall
+  #[test]
+  fn three_row_frame_is_sent_row_by_row() {
+    let rows = [[1u8, 2, 3, 4], [5, 6, 7, 8], [9, 10, 11, 12], [13, 14, 15, 16]];
+    let mut sent = Vec::new();
+
+    assert_eq!(stream_rows(rows.iter(), 4, 3, |row| sent.push(row.to_vec())), Ok(()));
+    assert_eq!(sent, [vec![1, 2, 3, 4], vec![5, 6, 7, 8], vec![9, 10, 11, 12]]);
+  }
+
+  #[test]
+  fn short_row_stops_the_frame() {
+    let rows: [&[u8]; 3] = [&[1, 2, 3, 4], &[5, 6], &[7, 8, 9, 10]];
+    let mut sent = Vec::new();
+
+    assert_eq!(stream_rows(rows.iter(), 4, 3, |row| sent.push(row.to_vec())), Err(DisplayError::LengthMismatch { expected: 4, length: 2 }));
+    assert_eq!(sent, [vec![1, 2, 3, 4]]);
+  }
+}
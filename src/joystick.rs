@@ -1,13 +1,24 @@
-use core::ops::{
-  BitAnd,
-  BitOr,
-  BitOrAssign
+pub use crate::buttons::{
+  ButtonSet,
+  JoystickCallback,
+  JoystickButton,
+  JoystickStates
+};
+use crate::buttons::{
+  JoystickAutofire,
+  JoystickCallbacks,
+  JoystickPresses,
+  JoystickState
+};
+use core::sync::atomic::{
+  AtomicU16,
+  Ordering
 };
 use embedded_hal::digital::v2::InputPin;
 use rp2040_hal::gpio::{
+  DynPin,
   Pin,
   PinId,
-  PullUpInput,
   bank0::{
     Gpio2,
     Gpio3,
@@ -21,50 +32,6 @@ use rp2040_hal::gpio::{
   }
 };
 
-#[repr(u16)]
-#[allow(dead_code)]
-pub enum JoystickButton {
-  A     = 0b0000000000000001, // 0x0001
-  B     = 0b0000000000000010, // 0x0002
-  X     = 0b0000000000000100, // 0x0004
-  Y     = 0b0000000000001000, // 0x0008
-  UP    = 0b0000000000010000, // 0x0010
-  DOWN  = 0b0000000000100000, // 0x0020
-  LEFT  = 0b0000000001000000, // 0x0040
-  RIGHT = 0b0000000010000000, // 0x0080
-  CTRL  = 0b0000000100000000  // 0x0100
-}
-
-impl BitAnd for JoystickButton {
-  type Output = u16;
-
-  fn bitand(self, rhs: Self) -> Self::Output {
-    self as u16 & rhs as u16
-  }
-}
-
-impl BitOr for JoystickButton {
-  type Output = u16;
-
-  fn bitor(self, rhs: Self) -> Self::Output {
-    self as u16 | rhs as u16
-  }
-}
-
-impl BitOr<JoystickButton> for u16 {
-  type Output = u16;
-
-  fn bitor(self, rhs: JoystickButton) -> Self::Output {
-    self | rhs as u16
-  }
-}
-
-impl BitOrAssign<JoystickButton> for u16 {
-  fn bitor_assign(&mut self, rhs: JoystickButton) {
-      *self |= rhs as u16
-  }
-}
-
 pub struct JoystickButtonsData {
   pub a    : Pin<Gpio15, <Gpio15 as PinId>::Reset>,
   pub b    : Pin<Gpio17, <Gpio17 as PinId>::Reset>,
@@ -77,147 +44,265 @@ pub struct JoystickButtonsData {
   pub ctrl : Pin<Gpio3 , <Gpio3  as PinId>::Reset>
 }
 
-struct JoystickButtons {
-  a    : Pin<Gpio15, PullUpInput>,
-  b    : Pin<Gpio17, PullUpInput>,
-  x    : Pin<Gpio19, PullUpInput>,
-  y    : Pin<Gpio21, PullUpInput>,
-  up   : Pin<Gpio2 , PullUpInput>,
-  down : Pin<Gpio18, PullUpInput>,
-  left : Pin<Gpio16, PullUpInput>,
-  right: Pin<Gpio20, PullUpInput>,
-  ctrl : Pin<Gpio3 , PullUpInput>
+// Internal resistor of the button inputs. It goes with the polarity given to
+// Joystick::with_pull: buttons shorting to ground need PullUp and read active
+// low, buttons shorting to 3V3 need PullDown and read active high, and None
+// leaves the pins floating for boards with external resistors
+#[derive(Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub enum JoystickPull {
+  PullUp,
+  PullDown,
+  None
 }
 
-type JoystickState = u16;
-
-struct JoystickStates {
-  current: JoystickState,
-  old    : JoystickState
+struct JoystickButtons {
+  a    : DynPin,
+  b    : DynPin,
+  x    : DynPin,
+  y    : DynPin,
+  up   : DynPin,
+  down : DynPin,
+  left : DynPin,
+  right: DynPin,
+  ctrl : DynPin
 }
 
 pub struct Joystick {
-  buttons: JoystickButtons,
-  states : JoystickStates
+  active_high: bool,
+  autofire   : JoystickAutofire,
+  buttons    : JoystickButtons,
+  callbacks  : JoystickCallbacks,
+  pending    : AtomicU16,
+  presses    : JoystickPresses,
+  states     : JoystickStates,
+  stuck      : ButtonSet
 }
 
 #[allow(dead_code)]
 impl Joystick {
+  // Pull-ups and buttons active low, as on the Pico LCD modules
   pub fn new(buttons: JoystickButtonsData) -> Self {
+    Self::with_pull(buttons, JoystickPull::PullUp, false)
+  }
+
+  pub fn with_pull(buttons: JoystickButtonsData, pull: JoystickPull, active_high: bool) -> Self {
     Self {
-      buttons: JoystickButtons {
-        a    : buttons.a    .into_pull_up_input(),
-        b    : buttons.b    .into_pull_up_input(),
-        x    : buttons.x    .into_pull_up_input(),
-        y    : buttons.y    .into_pull_up_input(),
-        up   : buttons.up   .into_pull_up_input(),
-        down : buttons.down .into_pull_up_input(),
-        left : buttons.left .into_pull_up_input(),
-        right: buttons.right.into_pull_up_input(),
-        ctrl : buttons.ctrl .into_pull_up_input()
+      active_high,
+      autofire   : JoystickAutofire::default(),
+      buttons    : JoystickButtons {
+        a    : input_pin(buttons.a    .into(), pull),
+        b    : input_pin(buttons.b    .into(), pull),
+        x    : input_pin(buttons.x    .into(), pull),
+        y    : input_pin(buttons.y    .into(), pull),
+        up   : input_pin(buttons.up   .into(), pull),
+        down : input_pin(buttons.down .into(), pull),
+        left : input_pin(buttons.left .into(), pull),
+        right: input_pin(buttons.right.into(), pull),
+        ctrl : input_pin(buttons.ctrl .into(), pull)
       },
-      states : JoystickStates {
-        current: 0x0000,
-        old    : 0x0000
-      }
+      callbacks  : JoystickCallbacks::default(),
+      pending    : AtomicU16::new(0x0000),
+      presses    : JoystickPresses::default(),
+      states     : JoystickStates::from_raw_state(0x0000, 0x0000),
+      stuck      : ButtonSet::EMPTY
     }
   }
 
+  // Call at startup with no button pressed: buttons reading active in every
+  // sample are disconnected or shorted, so they are logged and left out of
+  // is_any_active
+  pub fn calibrate(&mut self) {
+    let mut stuck = 0x01FF;
+
+    for _ in 0..8 {
+      stuck &= self.read_state();
+    }
+
+    self.stuck = ButtonSet::from_raw(stuck);
+
+    if stuck != 0 {
+      defmt::warn!("Joystick buttons stuck active: {=u16:#06x}", stuck);
+    }
+  }
+
+  pub fn stuck_buttons(&self) -> ButtonSet {
+    self.stuck
+  }
+
+  // (current, old) state words, see JoystickStates::raw_state
+  pub fn raw_state(&self) -> (u16, u16) {
+    self.states.raw_state()
+  }
+
+  // Registers the function called by update() when the button goes from
+  // released to pressed; None removes it. One callback per button
+  pub fn on_press(&mut self, button: JoystickButton, callback: Option<JoystickCallback>) {
+    self.callbacks.on_press(button, callback);
+  }
+
+  pub fn on_release(&mut self, button: JoystickButton, callback: Option<JoystickCallback>) {
+    self.callbacks.on_release(button, callback);
+  }
+
+  // While the button is held, every rate_ticks update/latch calls it shows up
+  // again as just pressed (and not held) for that call, with its press
+  // callback and in take_just_pressed. The first one comes after rate_ticks,
+  // with no longer initial delay; 0 turns it off
+  pub fn set_autofire(&mut self, button: JoystickButton, rate_ticks: u16) {
+    self.autofire.set(button, rate_ticks);
+  }
+
+  // Once per frame; the input consumers (ActionMap, IdleDimmer, TapCounter)
+  // are updated after it, so they all see the same snapshot
   pub fn update(&mut self) {
-    self.states.old = self.states.current;
+    self.apply_state(self.read_state());
+  }
+
+  // Reads the buttons and accumulates the pressed ones until the next latch.
+  // It only needs &self, so it can run from a timer interrupt (e.g. with the
+  // joystick shared through a cortex_m::interrupt::Mutex) between frames
+  pub fn sample(&self) {
+    let state = self.read_state();
+
+    cortex_m::interrupt::free(|_| {
+      self.pending.store(self.pending.load(Ordering::Relaxed) | state, Ordering::Relaxed);
+    });
+  }
+
+  // Takes the buttons accumulated by sample() plus the ones pressed now as the
+  // new current state. Latch exactly once per frame, before any input is read,
+  // and use either latch or update: every predicate then answers from the same
+  // snapshot for the whole frame, and a press shorter than a frame still
+  // shows up as just_pressed
+  pub fn latch(&mut self) {
+    let pending = cortex_m::interrupt::free(|_| {
+      let pending = self.pending.load(Ordering::Relaxed);
+
+      self.pending.store(0x0000, Ordering::Relaxed);
+
+      pending
+    });
+
+    self.apply_state(pending | self.read_state());
+  }
+
+  // Bitmask of the buttons pressed since the last call, which is cleared.
+  // Presses accumulate over any number of update/latch calls, so a button
+  // pressed and released between two reads is still reported once
+  pub fn take_just_pressed(&mut self) -> u16 {
+    self.presses.take_just_pressed()
+  }
+
+  // Marks the button as handled until the next update/latch, see
+  // JoystickStates::consume
+  pub fn consume(&mut self, button: JoystickButton) {
+    self.states.consume(button);
+  }
+
+  fn apply_state(&mut self, state: JoystickState) {
+    self.states.advance(state);
+    self.autofire.apply(&mut self.states);
 
+    self.presses.record(&self.states);
+
+    self.callbacks.dispatch(&self.states);
+  }
+
+  fn read_state(&self) -> JoystickState {
     let mut current_state: JoystickState = 0x0000;
 
-    if self.buttons.a.is_low().unwrap() {
+    if self.is_pin_active(&self.buttons.a) {
       current_state |= JoystickButton::A as u16;
     }
 
-    if self.buttons.b.is_low().unwrap() {
+    if self.is_pin_active(&self.buttons.b) {
       current_state |= JoystickButton::B as u16;
     }
 
-    if self.buttons.x.is_low().unwrap() {
+    if self.is_pin_active(&self.buttons.x) {
       current_state |= JoystickButton::X as u16;
     }
 
-    if self.buttons.y.is_low().unwrap() {
+    if self.is_pin_active(&self.buttons.y) {
       current_state |= JoystickButton::Y as u16;
     }
 
-    if self.buttons.up.is_low().unwrap() {
+    if self.is_pin_active(&self.buttons.up) {
       current_state |= JoystickButton::UP as u16;
     }
 
-    if self.buttons.down.is_low().unwrap() {
+    if self.is_pin_active(&self.buttons.down) {
       current_state |= JoystickButton::DOWN as u16;
     }
 
-    if self.buttons.left.is_low().unwrap() {
+    if self.is_pin_active(&self.buttons.left) {
       current_state |= JoystickButton::LEFT as u16;
     }
 
-    if self.buttons.right.is_low().unwrap() {
+    if self.is_pin_active(&self.buttons.right) {
       current_state |= JoystickButton::RIGHT as u16;
     }
 
-    if self.buttons.ctrl.is_low().unwrap() {
+    if self.is_pin_active(&self.buttons.ctrl) {
       current_state |= JoystickButton::CTRL as u16;
     }
 
-    self.states.current = current_state;
+    current_state
   }
 
-  pub fn is_active(&self, button: JoystickButton) -> bool {
-    let button_u16 = button as u16;
+  fn is_pin_active(&self, pin: &DynPin) -> bool {
+    if self.active_high {
+      pin.is_high().unwrap()
+    }
+    else {
+      pin.is_low().unwrap()
+    }
+  }
 
-    self.states.current & button_u16 == button_u16
+  pub fn states(&self) -> &JoystickStates {
+    &self.states
   }
 
-  pub fn just_pressed(&self, button: JoystickButton) -> bool {
-    let button_u16 = button as u16;
+  pub fn is_active(&self, button: JoystickButton) -> bool {
+    self.states.is_active(button)
+  }
 
-    self.states.old & button_u16 == 0 && self.states.current & button_u16 == button_u16
+  pub fn just_pressed(&self, button: JoystickButton) -> bool {
+    self.states.just_pressed(button)
   }
 
   pub fn just_released(&self, button: JoystickButton) -> bool {
-    let button_u16 = button as u16;
-
-    self.states.old & button_u16 == button_u16 && self.states.current & button_u16 == 0
+    self.states.just_released(button)
   }
 
   pub fn is_hold(&self, button: JoystickButton) -> bool {
-    let button_u16 = button as u16;
-
-    self.states.old & button_u16 == button_u16 && self.states.current & button_u16 == button_u16
+    self.states.is_hold(button)
   }
 
-  pub fn is_any_active(&self, buttons: Option<u16>) -> bool {
-    let buttons_value = buttons.unwrap_or(0x01FF);
+  pub fn is_any_active(&self, buttons: Option<ButtonSet>) -> bool {
+    self.states.is_any_active(Some(buttons.unwrap_or(ButtonSet::ALL).without(self.stuck)))
+  }
 
-    self.states.current & buttons_value != 0
+  pub fn is_combo_active(&self, buttons: ButtonSet) -> bool {
+    self.states.is_combo_active(buttons)
   }
 
   pub fn just_pressed_any(&self) -> bool {
-    self.just_pressed(JoystickButton::A) ||
-    self.just_pressed(JoystickButton::B) ||
-    self.just_pressed(JoystickButton::X) ||
-    self.just_pressed(JoystickButton::Y) ||
-    self.just_pressed(JoystickButton::UP) ||
-    self.just_pressed(JoystickButton::DOWN) ||
-    self.just_pressed(JoystickButton::LEFT) ||
-    self.just_pressed(JoystickButton::RIGHT) ||
-    self.just_pressed(JoystickButton::CTRL)
+    self.states.just_pressed_any()
   }
 
   pub fn just_released_any(&self) -> bool {
-    self.just_released(JoystickButton::A) ||
-    self.just_released(JoystickButton::B) ||
-    self.just_released(JoystickButton::X) ||
-    self.just_released(JoystickButton::Y) ||
-    self.just_released(JoystickButton::UP) ||
-    self.just_released(JoystickButton::DOWN) ||
-    self.just_released(JoystickButton::LEFT) ||
-    self.just_released(JoystickButton::RIGHT) ||
-    self.just_released(JoystickButton::CTRL)
+    self.states.just_released_any()
   }
 }
+
+fn input_pin(mut pin: DynPin, pull: JoystickPull) -> DynPin {
+  match pull {
+    JoystickPull::PullUp   => pin.into_pull_up_input(),
+    JoystickPull::PullDown => pin.into_pull_down_input(),
+    JoystickPull::None     => pin.into_floating_input()
+  }
+
+  pin
+}
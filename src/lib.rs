@@ -0,0 +1,30 @@
+// The hardware independent modules, also built as a library so their tests
+// run on the host with `cargo test-host` (an alias in .cargo/config.toml).
+// The firmware binary declares the same modules and doesn't use it. Display
+// and the pin side of Joystick need the RP2040 peripherals, so they aren't
+// part of it
+#![cfg_attr(not(test), no_std)]
+
+pub mod actions;
+pub mod animation;
+pub mod bus;
+pub mod buttons;
+pub mod color;
+pub mod dial;
+pub mod error;
+pub mod fmt;
+pub mod font;
+pub mod framebuffer;
+pub mod geometry;
+pub mod idle;
+pub mod image;
+pub mod palette;
+pub mod pattern;
+pub mod registers;
+pub mod scroll;
+pub mod slideshow;
+pub mod sprite;
+pub mod taps;
+pub mod text;
+pub mod timing;
+pub mod trig;
@@ -1,22 +1,57 @@
 #![no_std]
 #![no_main]
 
+mod actions;
+mod animation;
+mod bus;
+mod buttons;
+mod color;
+mod console;
+mod dial;
 mod display;
+mod error;
+mod fmt;
 mod font;
+mod frame;
+mod framebuffer;
+mod geometry;
+mod idle;
+mod image;
 mod joystick;
+mod palette;
+mod pattern;
 mod photos;
+mod registers;
+mod scroll;
+mod slideshow;
+mod sprite;
+mod taps;
+mod text;
+mod ticker;
+mod timing;
+mod trig;
+mod widgets;
 
 use cortex_m_rt::entry;
 use defmt_rtt as _;
 use display::{
   Display,
   DisplayColorModeBPP,
+  DisplayConfig,
   DisplayPinsData,
   DisplayRotation,
   DisplaySpiData
 };
+use frame::{
+  FrameContext,
+  FrameGuard
+};
 use fugit::RateExtU32;
-use heapless::String;
+use geometry::{
+  clamp_rect_to_bounds,
+  Rect,
+  Size
+};
 use joystick::{
   Joystick,
   JoystickButton,
@@ -24,6 +59,9 @@ use joystick::{
 };
 use panic_probe as _;
 use rp2040_hal as hal;
+use slideshow::Slideshow;
+use timing::FpsCounter;
+use widgets::StatusLine;
 
 use hal::{
   clocks::{
@@ -69,12 +107,14 @@ fn main() -> ! {
   );
 
   let display_pins_data = DisplayPinsData {
-    backlight   : pins.gpio13,
-    chip_select : pins.gpio9,
-    data_command: pins.gpio8,
-    reset       : pins.gpio12,
-    spi_clock   : pins.gpio10,
-    spi_mosi    : pins.gpio11
+    backlight     : pins.gpio13,
+    chip_select   : pins.gpio9,
+    data_command  : pins.gpio8,
+    reset         : pins.gpio12,
+    spi_clock     : pins.gpio10,
+    spi_miso      : None,
+    spi_mosi      : pins.gpio11,
+    tearing_effect: None
   };
 
   let display_spi_data = DisplaySpiData {
@@ -85,29 +125,34 @@ fn main() -> ! {
     resets: &mut pac.RESETS
   };
 
-  let mut display = Display::new(
-    240,
-    240,
+  let background_color: u32 = 0b00110_001101_00110;
+  let foreground_color: u32 = 0b00000_101100_00000;
+
+  let display_config = DisplayConfig {
+    clear_on_init        : false,
+    text_background_color: Some(background_color),
+    text_foreground_color: foreground_color,
+    text_scale           : 2,
+    ..DisplayConfig::default()
+  };
+
+  let mut display: Display = Display::new(
+    Size::new(240, 240),
     DisplayColorModeBPP::BPP16,
     DisplayRotation::Landscape,
     display_pins_data,
     display_spi_data,
+    display_config,
     &mut delay
   );
 
-  let background_color: u32 = 0b00110_001101_00110;
-  let foreground_color: u32 = 0b00000_101100_00000;
-
   display.fill(background_color);
-  display.set_text_foreground_color(foreground_color);
-  display.set_text_background_color(Some(background_color));
-  display.set_text_pixel_height(2);
-  display.set_text_pixel_width(2);
 
-  display.set_window(0, 0, 239, 239);
-  display.send_data(&photos::PHOTOS[0]);
+  display.present_frame(&photos::PHOTOS[0]).unwrap();
 
-  display.draw_text(5, 5, String::from("0"));
+  let mut status_line: StatusLine<31> = StatusLine::new(5, 5);
+
+  status_line.draw(&mut display, "0");
 
   let joystick_buttons_data = JoystickButtonsData {
     a    : pins.gpio15,
@@ -123,128 +168,128 @@ fn main() -> ! {
 
   let mut joystick = Joystick::new(joystick_buttons_data);
 
+  joystick.calibrate();
+
   let mut x = 0u16;
   let mut y = 0u16;
   let mut w = 0u16;
   let mut h = 0u16;
 
-  let mut photo: i8 = -1;
-  let hidden_photo_index = 4;
+  let mut slideshow = Slideshow::new(&photos::PHOTOS[..]);
+
+  slideshow.exclude(4);
+
   let mut ctrl_hold_counter = 0;
   let ctrl_hold_counter_threshold = 4_000_000;
 
   let timer = hal::Timer::new(pac.TIMER, &mut pac.RESETS);
-  let mut time_old: u32 = timer.get_counter_low();
-  let mut fps = 0;
+  let mut fps_counter = FpsCounter::new(timer.get_counter_low());
 
-  loop {
-    let time_start: u32 = timer.get_counter_low();
-    let time_diff = time_start - time_old;
-
-    if time_diff >= 1000000 {
-      let mut fps_str: String<31> = String::from("FPS:\n");
+  let mut frame_guard = FrameGuard::new(watchdog, 2_000_000, 500_000);
 
-      fps_str.push_str(String::<31>::from(fps).as_str()).unwrap();
+  frame_guard.on_frame_complete(Some(log_frame));
 
-      display.draw_text(5, 5, fps_str);
+  loop {
+    let time_start: u32 = timer.get_counter_low();
 
-      time_old = time_start;
+    frame_guard.run(&mut display, &timer, &mut delay, |display| {
+      if fps_counter.tick(time_start) {
+        let mut fps_buf = [0u8; 16];
+        let fps_str = fmt::format_labeled_u32_into(&mut fps_buf, "FPS:\n", fps_counter.fps());
 
-      fps = 0;
-    }
+        status_line.draw(display, fps_str);
+      }
 
-    joystick.update();
+      joystick.update();
 
-    if joystick.is_active(JoystickButton::A) {
-      if w > 0 {
-        w -= 1;
+      if joystick.is_active(JoystickButton::A) {
+        w = w.saturating_sub(1);
       }
-    }
 
-    if joystick.is_active(JoystickButton::B) {
-      if w + x < display.width() - 1 {
-        w += 1;
+      if joystick.is_active(JoystickButton::B) {
+        w = w.saturating_add(1);
       }
-    }
 
-    if joystick.is_active(JoystickButton::X) {
-      if h > 0 {
-        h -= 1;
+      if joystick.is_active(JoystickButton::X) {
+        h = h.saturating_sub(1);
       }
-    }
 
-    if joystick.is_active(JoystickButton::Y) {
-      if h + y < display.height() - 1 {
-        h += 1;
+      if joystick.is_active(JoystickButton::Y) {
+        h = h.saturating_add(1);
       }
-    }
 
-    if joystick.is_active(JoystickButton::UP) {
-      if y > 0 {
-        y -= 1;
+      if joystick.is_active(JoystickButton::UP) {
+        y = y.saturating_sub(1);
       }
-    }
 
-    if joystick.is_active(JoystickButton::DOWN) {
-      if y + h < display.height() - 1 {
-        y += 1;
+      if joystick.is_active(JoystickButton::DOWN) {
+        y = y.saturating_add(1);
       }
-    }
 
-    if joystick.is_active(JoystickButton::LEFT) {
-      if x > 0 {
-        x -= 1;
+      if joystick.is_active(JoystickButton::LEFT) {
+        x = x.saturating_sub(1);
       }
-    }
 
-    if joystick.is_active(JoystickButton::RIGHT) {
-      if x + w < display.width() - 1 {
-        x += 1;
+      if joystick.is_active(JoystickButton::RIGHT) {
+        x = x.saturating_add(1);
       }
-    }
 
-    if joystick.is_active(JoystickButton::CTRL) {
-      let time_now: u32 = timer.get_counter_low() / 1000000;
-      let time_str: String<31> = String::from(time_now);
+      let rect = clamp_rect_to_bounds(Rect::new(x, y, w, h), display.width(), display.height());
+
+      (x, y, w, h) = (rect.x, rect.y, rect.width, rect.height);
+
+      if joystick.is_active(JoystickButton::CTRL) {
+        let time_now: u32 = timer.get_counter_low() / 1000000;
+        let mut time_buf = [0u8; fmt::U32_MAX_LENGTH];
+        let time_str = fmt::format_u32_into(&mut time_buf, time_now);
 
-      display.draw_text(5, 5, time_str);
-    }
+        status_line.draw(display, time_str);
+      }
 
-    if joystick.just_pressed(JoystickButton::CTRL) {
-      photo += 1;
-      photo %= photos::PHOTOS.len() as i8;
+      if joystick.just_pressed(JoystickButton::CTRL) {
+        if let Some(photo) = slideshow.next() {
+          display.present_frame(photo).unwrap();
+        }
 
-      if photo == hidden_photo_index {
-        photo += 1;
-        photo %= photos::PHOTOS.len() as i8;
+        status_line.invalidate();
       }
+      else if joystick.is_hold(JoystickButton::CTRL) {
+        ctrl_hold_counter += 1;
 
-      display.set_window(0, 0, 239, 239);
-      display.send_data(&photos::PHOTOS[photo as usize]);
-    }
-    else if joystick.is_hold(JoystickButton::CTRL) {
-      ctrl_hold_counter += 1;
+        if ctrl_hold_counter > ctrl_hold_counter_threshold {
+          if let Some(photo) = slideshow.show_hidden() {
+            display.present_frame(photo).unwrap();
+          }
 
-      if ctrl_hold_counter > ctrl_hold_counter_threshold {
-        display.set_window(0, 0, 239, 239);
-        display.send_data(&photos::PHOTOS[hidden_photo_index as usize]);
+          status_line.invalidate();
+        }
       }
-    }
-    else if joystick.just_released(JoystickButton::CTRL) {
-      ctrl_hold_counter = 0;
+      else if joystick.just_released(JoystickButton::CTRL) {
+        ctrl_hold_counter = 0;
+
+        if let Some(photo) = slideshow.current() {
+          display.present_frame(photo).unwrap();
+        }
 
-      display.set_window(0, 0, 239, 239);
-      display.send_data(&photos::PHOTOS[photo as usize]);
-    }
-    else if joystick.is_any_active(Some(JoystickButton::A | JoystickButton::B | JoystickButton::X | JoystickButton::Y | JoystickButton::UP | JoystickButton::DOWN | JoystickButton::LEFT | JoystickButton::RIGHT)) {
-      display.fill(0);
-      display.draw_solid_rect(x, y, w, h, 0b1111100000000000);
-    }
+        status_line.invalidate();
+      }
+      else if joystick.is_any_active(Some(JoystickButton::A | JoystickButton::B | JoystickButton::X | JoystickButton::Y | JoystickButton::UP | JoystickButton::DOWN | JoystickButton::LEFT | JoystickButton::RIGHT)) {
+        display.fill(0);
+        display.draw_solid_rect(x, y, w, h, 0b1111100000000000);
 
-    fps += 1;
+        status_line.invalidate();
+      }
+    });
 
     let time_end = timer.get_counter_low();
 
-    delay.delay_us(1000000 / 15 - (time_end - time_start));
+    delay.delay_us((1_000_000u32 / 15).saturating_sub(timing::elapsed(time_end, time_start)));
+  }
+}
+
+// Frame time every 10s at 15 FPS, to spot frames creeping towards the budget
+fn log_frame(context: &FrameContext) {
+  if context.frame.is_multiple_of(150) {
+    defmt::debug!("Frame {=u32} took {=u32}us", context.frame, context.elapsed_us);
   }
 }
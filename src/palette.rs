@@ -0,0 +1,66 @@
+// Colors for indexed images (one byte per pixel). Cycling rotates which color
+// every index maps to, the classic way to animate water or fire by redrawing
+// the same image instead of computing new pixels
+pub struct Palette<const N: usize> {
+  colors: [u32; N],
+  offset: usize
+}
+
+#[allow(dead_code)]
+impl<const N: usize> Palette<N> {
+  pub fn new(colors: [u32; N]) -> Self {
+    Self {
+      colors,
+      offset: 0
+    }
+  }
+
+  // Index i now shows the color index i + 1 showed before
+  pub fn cycle(&mut self) {
+    if N > 0 {
+      self.offset = (self.offset + 1) % N;
+    }
+  }
+
+  // Indexes past the palette wrap around; an empty palette gives black
+  pub fn color(&self, index: u8) -> u32 {
+    if N == 0 {
+      return 0;
+    }
+
+    self.colors[(index as usize + self.offset) % N]
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn cycle_rotates_the_mapping() {
+    let mut palette = Palette::new([0x000000, 0xFF0000, 0x00FF00]);
+
+    assert_eq!(palette.color(0), 0x000000);
+    assert_eq!(palette.color(4), 0xFF0000);
+
+    palette.cycle();
+
+    assert_eq!(palette.color(0), 0xFF0000);
+    assert_eq!(palette.color(1), 0x00FF00);
+    assert_eq!(palette.color(2), 0x000000);
+
+    palette.cycle();
+    palette.cycle();
+
+    assert_eq!(palette.color(0), 0x000000);
+  }
+
+  #[test]
+  fn empty_palette_gives_black() {
+    let mut palette = Palette::new([]);
+
+    palette.cycle();
+
+    assert_eq!(palette.color(7), 0);
+  }
+}
@@ -0,0 +1,73 @@
+use crate::geometry::Rect;
+
+// The 8x8 fill pattern of set_fill_pattern and the runs it paints
+
+// Whether the pattern paints the screen pixel (x, y): bit 7 of each row byte
+// is the leftmost column and the pattern repeats over the screen grid
+pub fn pattern_bit(pattern: &[u8; 8], x: u16, y: u16) -> bool {
+  pattern[(y & 7) as usize] & (0x80 >> (x & 7)) != 0
+}
+
+// The 1 pixel tall runs of set pattern bits inside rect, row by row
+pub fn pattern_runs(pattern: &[u8; 8], rect: Rect) -> impl Iterator<Item = Rect> + '_ {
+  let end_x = rect.x.saturating_add(rect.width);
+  let end_y = rect.y.saturating_add(rect.height);
+
+  let mut pixel_x = rect.x;
+  let mut pixel_y = rect.y;
+
+  core::iter::from_fn(move || {
+    while pixel_y < end_y {
+      while pixel_x < end_x && !pattern_bit(pattern, pixel_x, pixel_y) {
+        pixel_x += 1;
+      }
+
+      if pixel_x < end_x {
+        let start = pixel_x;
+
+        while pixel_x < end_x && pattern_bit(pattern, pixel_x, pixel_y) {
+          pixel_x += 1;
+        }
+
+        return Some(Rect::new(start, pixel_y, pixel_x - start, 1));
+      }
+
+      pixel_x = rect.x;
+      pixel_y += 1;
+    }
+
+    None
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const DIAGONAL: [u8; 8] = [0x80, 0x40, 0x20, 0x10, 0x08, 0x04, 0x02, 0x01];
+
+  // The diagonal stipple over a 4x3 rect, '#' being a set bit
+  #[test]
+  fn diagonal_stipple_mask() {
+    let mask: Vec<String> = (0..3)
+      .map(|y| (0..4).map(|x| if pattern_bit(&DIAGONAL, x, y) { '#' } else { '.' }).collect())
+      .collect();
+
+    assert_eq!(mask, ["#...", ".#..", "..#."]);
+  }
+
+  // Row 8 starts the pattern over, so (8, 8) is set again
+  #[test]
+  fn diagonal_runs_wrap_around_the_grid() {
+    let runs: Vec<Rect> = pattern_runs(&DIAGONAL, Rect::new(6, 6, 4, 3)).collect();
+
+    assert_eq!(runs, [Rect::new(6, 6, 1, 1), Rect::new(7, 7, 1, 1), Rect::new(8, 8, 1, 1)]);
+  }
+
+  #[test]
+  fn set_bits_next_to_each_other_are_one_run() {
+    let runs: Vec<Rect> = pattern_runs(&[0xF0; 8], Rect::new(2, 0, 10, 1)).collect();
+
+    assert_eq!(runs, [Rect::new(2, 0, 2, 1), Rect::new(8, 0, 4, 1)]);
+  }
+}
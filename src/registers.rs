@@ -0,0 +1,381 @@
+use core::ops::BitOr;
+use crate::error::DisplayError;
+
+// ST7789 commands and their parameter values
+
+// https://datasheets.raspberrypi.com/rp2040/rp2040-datasheet.pdf
+// https://www.rhydolabz.com/documents/33/ST7789.pdf
+#[repr(u8)]
+#[allow(dead_code)]
+pub enum DisplayCommand {
+  NOP        = 0x00, // Do nothing
+  SWRESET    = 0x01, // Software reset
+  RDDID      = 0x04, // Read display ID
+  RDDST      = 0x09, // Read display status
+  RDDPM      = 0x0A, // Read display power mode
+  RDDMADCTL  = 0x0B, // Read display MADCTL
+  RDDCOLMOD  = 0x0C, // Read display pixel format
+  RDDIM      = 0x0D, // Read display image format
+  RDDSM      = 0x0E, // Read display signal mode
+  RDDSDR     = 0x0F, // Read display self-diagnostic result
+  SLPIN      = 0x10, // Sleep in
+  SLPOUT     = 0x11, // Sleep out
+  PTLON      = 0x12, // Partial display mode on
+  NORON      = 0x13, // Normal display mode on
+  INVOFF     = 0x20, // Display inversion off
+  INVON      = 0x21, // Display inversion on
+  GAMSET     = 0x26, // Gamma set
+  DISPOFF    = 0x28, // Display off
+  DISPON     = 0x29, // Display on
+  CASET      = 0x2A, // Column address set
+  RASET      = 0x2B, // Row address set
+  RAMWR      = 0x2C, // Memory write
+  RAMRD      = 0x2E, // Memory read
+  PTLAR      = 0x30, // Partial area
+  VSCRDEF    = 0x33, // Vertical scrolling definition
+  TEOFF      = 0x34, // Tearing effect line off
+  TEON       = 0x35, // Tearing effect line on
+  MADCTL     = 0x36, // Memory data access control
+  VSCSAD     = 0x37, // Vertical scroll start address of RAM
+  IDMOFF     = 0x38, // Idle mode off
+  IDMON      = 0x39, // Idle mode on
+  COLMOD     = 0x3A, // Interface pixel format
+  WRMEMC     = 0x3C, // Write memory continue
+  RDMEMC     = 0x3E, // Read memory continue
+  STE        = 0x44, // Set tear scanline
+  GSCAN      = 0x45, // Get scanline
+  WRDISBV    = 0x51, // Write display brightness
+  RDDISBV    = 0x52, // Read display brightness value
+  WRCTRLD    = 0x53, // Write CTRL display
+  RDCTRLD    = 0x54, // Read CTRL value display
+  WRCACE     = 0x55, // Write content adaptive brightness control and color enhancement
+  RDCABC     = 0x56, // Read content adaptive brightness control
+  WRCABCMB   = 0x5E, // Write CACB minimum brightness
+  RDCABCMB   = 0x5F, // Read CACB minimum brightness
+  RDABCSDR   = 0x68, // Read automatic brightness control self-diagnostic result
+  RAMCTRL    = 0xB0, // RAM control
+  RGBCTRL    = 0xB1, // RGB interface control
+  PORCTRL    = 0xB2, // Porch setting
+  FRCTRL1    = 0xB3, // Frame rate control 1 (in partial mode/idle colors)
+  PARCTRL    = 0xB5, // Partial control
+  GCTRL      = 0xB7, // Gate control
+  GTADJ      = 0xB8, // Gate on timing adjustment
+  DGMEN      = 0xBA, // Digital gamma enable
+  VCOMS      = 0xBB, // VCOM setting
+  POWSAVE    = 0xBC, // Power saving mode
+  DLPOFFSAVE = 0xBD, // Display off power save
+  LCMCTRL    = 0xC0, // LCM control
+  IDSET      = 0xC1, // ID code setting
+  VDVVRHEN   = 0xC2, // VDV and VRH command enable
+  VRHS       = 0xC3, // VRH set
+  VDVS       = 0xC4, // VDV set
+  VCMOFSET   = 0xC5, // VCOM offset set
+  FRCTRL2    = 0xC6, // Frame rate control in normal mode
+  CABCCTRL   = 0xC7, // CABC control
+  REGSEL1    = 0xC8, // Register value selection 1
+  REGSEL2    = 0xCA, // Register value selection 2
+  PWMFRSEL   = 0xCC, // PWM frequency selection
+  PWCTRL1    = 0xD0, // Power control 1
+  VAPVANEN   = 0xD2, // Enable VAP/VAN signal output
+  RDID1      = 0xDA, // Read ID1
+  RDID2      = 0xDB, // Read ID2
+  RDID3      = 0xDC, // Read ID3
+  CMD2EN     = 0xDF, // Command 2 enable
+  PVGAMCTRL  = 0xE0, // Positive voltage gamma control
+  NVGAMCTRL  = 0xE1, // Negative voltage gamma control
+  DGMLUTR    = 0xE2, // Digital gamma look-up table for red
+  DGMLUTB    = 0xE3, // Digital gamma look-up table for blue
+  GATECTRL   = 0xE4, // Gate control
+  SPI2EN     = 0xE7, // SPI2 enable
+  PWCTRL2    = 0xE8, // Power control 2
+  EQCTRL     = 0xE9, // Equalize time control
+  PROMCTRL   = 0xEC, // Program mode control
+  PROMEN     = 0xFA, // Program mode enable
+  NVMSET     = 0xFC, // NVM setting
+  PROMACT    = 0xFE  // Program action
+}
+
+#[repr(u8)]
+#[allow(dead_code)]
+pub enum DisplayColorMode {
+  BPP12   = 0b00000011, // 12 bits/pixel
+  BPP16   = 0b00000101, // 16 bits/pixel -> 0bRRRRRGGG_GGGBBBBB
+  BPP18   = 0b00000110, // 18 bits/pixel -> 0bRRRRRR00_GGGGGG00_BBBBBB00
+  BPP16M  = 0b00000111, // 16M truncated -> 0bRRRRR000_GGGGGG00_BBBBB000
+  RGB65K  = 0b01010000, // 65K of RGB interface
+  RGB262K = 0b01100000  // 262K of RGB interface
+}
+
+impl BitOr for DisplayColorMode {
+  type Output = u8;
+
+  fn bitor(self, rhs: Self) -> Self::Output {
+    self as u8 | rhs as u8
+  }
+}
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub enum DisplayColorModeBPP {
+  BPP12,  // 12 bits/pixel -> 0bRRRRGGGG_BBBB (two pixels in three bytes)
+  BPP16,  // 16 bits/pixel -> 0bRRRRRGGG_GGGBBBBB
+  BPP18,  // 18 bits/pixel -> 0bRRRRRR00_GGGGGG00_BBBBBB00
+  BPP16M, // 16M truncated -> 0bRRRRR000_GGGGGG00_BBBBB000
+  UNKNOWN 
+}
+
+#[repr(u8)]
+#[allow(dead_code)]
+pub enum DisplayMADCTL {
+  MH  = 0b00000100, // Display data latch order
+  RGB = 0b00001000, // RGB/BGR order
+  ML  = 0b00010000, // Line address order
+  MV  = 0b00100000, // Page/column order
+  MX  = 0b01000000, // Column address order
+  MY  = 0b10000000  // Page address order
+}
+
+impl BitOr for DisplayMADCTL {
+  type Output = u8;
+
+  fn bitor(self, rhs: Self) -> Self::Output {
+    self as u8 | rhs as u8
+  }
+}
+
+// Every MADCTL bit besides the rotation, so the setters sharing the register
+// don't clobber each other. MX and MY here flip the ones of the rotation,
+// which supplies MV, MX and MY; bits() composes the byte sent to the panel
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Madctl(u8);
+
+#[allow(dead_code)]
+impl Madctl {
+  pub const fn new() -> Self {
+    Self(0)
+  }
+
+  pub fn bits(self, rotation: DisplayRotation) -> u8 {
+    let flags = DisplayMADCTL::MH | DisplayMADCTL::RGB | DisplayMADCTL::ML as u8;
+    let mirror = DisplayMADCTL::MX | DisplayMADCTL::MY;
+
+    self.0 & flags | (rotation as u8 ^ self.0 & mirror)
+  }
+
+  pub fn bgr(self, on: bool) -> Self {
+    self.with(DisplayMADCTL::RGB, on)
+  }
+
+  pub fn latch_order_reversed(self, on: bool) -> Self {
+    self.with(DisplayMADCTL::MH, on)
+  }
+
+  pub fn line_order_reversed(self, on: bool) -> Self {
+    self.with(DisplayMADCTL::ML, on)
+  }
+
+  pub fn mirror_x(self, on: bool) -> Self {
+    self.with(DisplayMADCTL::MX, on)
+  }
+
+  pub fn mirror_y(self, on: bool) -> Self {
+    self.with(DisplayMADCTL::MY, on)
+  }
+
+  fn with(self, bit: DisplayMADCTL, on: bool) -> Self {
+    if on {
+      Self(self.0 | bit as u8)
+    }
+    else {
+      Self(self.0 & !(bit as u8))
+    }
+  }
+}
+
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub enum DisplayRotation {
+  Portrait          = 0b00000000,
+  Landscape         = 0b01100000, // DisplayMADCTL::MV | DisplayMADCTL::MX
+  InvertedLandscape = 0b10100000, // DisplayMADCTL::MV | DisplayMADCTL::MY
+  InvertedPortrait  = 0b11000000  // DisplayMADCTL::MX | DisplayMADCTL::MY
+}
+
+#[allow(dead_code)]
+impl DisplayRotation {
+  // The (width, height) of a width x height screen in rotation once turned to
+  // this one: they swap between portrait and landscape (MV toggles)
+  pub fn resolution_from(self, rotation: DisplayRotation, width: u16, height: u16) -> (u16, u16) {
+    if (self as u8 ^ rotation as u8) & DisplayMADCTL::MV as u8 != 0 {
+      (height, width)
+    }
+    else {
+      (width, height)
+    }
+  }
+}
+
+// Init sequence run after the hard reset: each step is a command byte, its
+// parameters and the ms to wait after it (0 for none). COLMOD and MADCTL with
+// no parameters take them from the Display state (color mode, rotation and
+// the MADCTL flags). After the table init sends TEON with a TE pin (TEOFF
+// without one) and RAMCTRL when DisplayConfig::ram_control is set. Panels
+// that need other power or gamma settings pass their own table in
+// DisplayConfig::init_table; it must leave the panel out of sleep with the
+// display off, and keep the COLMOD and MADCTL steps the drawing relies on
+pub type DisplayInitStep = (u8, &'static [u8], u32);
+
+pub const DISPLAY_INIT_TABLE: &[DisplayInitStep] = &[
+  (DisplayCommand::SWRESET as u8, &[], 150),
+  (DisplayCommand::SLPOUT as u8,  &[], 0),
+  (DisplayCommand::COLMOD as u8,  &[], 0),
+  (DisplayCommand::MADCTL as u8,  &[], 0),
+  (DisplayCommand::INVON as u8,   &[], 0),
+  (DisplayCommand::NORON as u8,   &[], 0)
+];
+
+// The color mode set_bpp configures and keeps track of: UNKNOWN falls back to
+// BPP16, as color_mode_bits does, so the tracked mode matches the panel
+pub fn configured_bpp(bpp: DisplayColorModeBPP) -> DisplayColorModeBPP {
+  match bpp {
+    DisplayColorModeBPP::UNKNOWN => DisplayColorModeBPP::BPP16,
+    bpp                          => bpp
+  }
+}
+
+// COLMOD parameter for the color mode; UNKNOWN gets the BPP16 one
+pub fn color_mode_bits(bpp: DisplayColorModeBPP) -> u8 {
+  match bpp {
+    DisplayColorModeBPP::BPP12   => DisplayColorMode::RGB65K  | DisplayColorMode::BPP12,
+    DisplayColorModeBPP::BPP16   => DisplayColorMode::RGB65K  | DisplayColorMode::BPP16,
+    DisplayColorModeBPP::BPP16M  => DisplayColorMode::RGB65K  | DisplayColorMode::BPP16M,
+    DisplayColorModeBPP::BPP18   => DisplayColorMode::RGB262K | DisplayColorMode::BPP18,
+    DisplayColorModeBPP::UNKNOWN => color_mode_bits(DisplayColorModeBPP::BPP16)
+  }
+}
+
+// Command that takes the output from blanked to blank: DISPOFF to blank and
+// DISPON to unblank, None when it is already there
+pub fn blank_command(blanked: bool, blank: bool) -> Option<DisplayCommand> {
+  match (blanked, blank) {
+    (false, true) => Some(DisplayCommand::DISPOFF),
+    (true, false) => Some(DisplayCommand::DISPON),
+    _             => None
+  }
+}
+
+// GCTRL parameter: VGH in bits 6-4 and VGL in bits 2-0, bits 7 and 3 are
+// reserved and must be 0
+pub fn gate_control(value: u8) -> Result<u8, DisplayError> {
+  const RESERVED: u8 = 0x88;
+
+  if value & RESERVED != 0 {
+    return Err(DisplayError::ReservedBitsSet { value, mask: RESERVED });
+  }
+
+  Ok(value)
+}
+
+// GTADJ parameters: the fixed keys 0x2A and 0x2B, then the gate on timing GTA
+// (0x00..=0x3F) and the gate off timing GOF/GOFR (high/low nibble)
+pub fn gate_timing_adjustment(gate_on: u8, gate_off: u8) -> Result<[u8; 4], DisplayError> {
+  if gate_on > 0x3F {
+    return Err(DisplayError::ParameterOutOfRange { value: gate_on, max: 0x3F });
+  }
+
+  Ok([0x2A, 0x2B, gate_on, gate_off])
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // The sequence of the init before it was table driven: SWRESET (150ms),
+  // SLPOUT, COLMOD, MADCTL, INVON and NORON
+  #[test]
+  fn default_init_table_matches_the_previous_init() {
+    let commands: [(u8, usize, u32); 6] = [
+      (0x01, 0, 150),
+      (0x11, 0, 0),
+      (0x3A, 0, 0),
+      (0x36, 0, 0),
+      (0x21, 0, 0),
+      (0x13, 0, 0)
+    ];
+
+    assert_eq!(DISPLAY_INIT_TABLE.len(), commands.len());
+
+    for (&(command, params, delay_ms), &expected) in DISPLAY_INIT_TABLE.iter().zip(commands.iter()) {
+      assert_eq!((command, params.len(), delay_ms), expected);
+    }
+  }
+
+  #[test]
+  fn colmod_for_each_bpp() {
+    assert_eq!(color_mode_bits(DisplayColorModeBPP::BPP12), 0x53);
+    assert_eq!(color_mode_bits(DisplayColorModeBPP::BPP16), 0x55);
+    assert_eq!(color_mode_bits(DisplayColorModeBPP::BPP16M), 0x57);
+    assert_eq!(color_mode_bits(DisplayColorModeBPP::BPP18), 0x66);
+    assert_eq!(color_mode_bits(DisplayColorModeBPP::UNKNOWN), 0x55);
+  }
+
+  #[test]
+  fn rotation_mirror_and_bgr_compose() {
+    let madctl = Madctl::new().mirror_x(true).bgr(true);
+
+    // Landscape's MX is flipped back off, MV stays
+    assert_eq!(madctl.bits(DisplayRotation::Landscape), 0b00101000);
+    assert_eq!(madctl.bits(DisplayRotation::Portrait), 0b01001000);
+    assert_eq!(madctl.mirror_y(true).bits(DisplayRotation::InvertedLandscape), 0b01101000);
+    assert_eq!(madctl.bgr(false).bits(DisplayRotation::InvertedPortrait), 0b10000000);
+  }
+
+  #[test]
+  fn latch_and_line_order_compose() {
+    let madctl = Madctl::new().latch_order_reversed(true).line_order_reversed(true);
+
+    assert_eq!(madctl.bits(DisplayRotation::Portrait), 0b00010100);
+    assert_eq!(madctl.bits(DisplayRotation::Landscape), 0b01110100);
+    assert_eq!(madctl.latch_order_reversed(false).bits(DisplayRotation::Portrait), 0b00010000);
+    assert_eq!(madctl.line_order_reversed(false).bgr(true).bits(DisplayRotation::Portrait), 0b00001100);
+  }
+
+  // set_rotation from a 240x320 portrait screen
+  #[test]
+  fn rotation_swaps_the_resolution_between_portrait_and_landscape() {
+    let portrait = DisplayRotation::Portrait;
+
+    assert_eq!(DisplayRotation::Portrait.resolution_from(portrait, 240, 320), (240, 320));
+    assert_eq!(DisplayRotation::Landscape.resolution_from(portrait, 240, 320), (320, 240));
+    assert_eq!(DisplayRotation::InvertedLandscape.resolution_from(portrait, 240, 320), (320, 240));
+    assert_eq!(DisplayRotation::InvertedPortrait.resolution_from(portrait, 240, 320), (240, 320));
+    assert_eq!(DisplayRotation::InvertedLandscape.resolution_from(DisplayRotation::Landscape, 320, 240), (320, 240));
+  }
+
+  #[test]
+  fn gate_control_rejects_the_reserved_bits() {
+    assert_eq!(gate_control(0x08), Err(DisplayError::ReservedBitsSet { value: 0x08, mask: 0x88 }));
+    assert_eq!(gate_control(0x80), Err(DisplayError::ReservedBitsSet { value: 0x80, mask: 0x88 }));
+    assert_eq!(gate_control(0x75), Ok(0x75));
+    assert_eq!(gate_control(0x35), Ok(0x35));
+  }
+
+  #[test]
+  fn gate_timing_adjustment_keeps_the_keys() {
+    assert_eq!(gate_timing_adjustment(0x22, 0x75), Ok([0x2A, 0x2B, 0x22, 0x75]));
+    assert_eq!(gate_timing_adjustment(0x40, 0x75), Err(DisplayError::ParameterOutOfRange { value: 0x40, max: 0x3F }));
+  }
+
+  // Drawing skips UNKNOWN, so set_bpp must never keep it
+  #[test]
+  fn unknown_bpp_is_configured_as_bpp16() {
+    let bpp = configured_bpp(DisplayColorModeBPP::UNKNOWN);
+
+    assert!(bpp == DisplayColorModeBPP::BPP16);
+    assert_eq!(crate::color::bytes_per_pixel(bpp), 2);
+    assert_eq!(color_mode_bits(bpp), color_mode_bits(DisplayColorModeBPP::UNKNOWN));
+    assert!(configured_bpp(DisplayColorModeBPP::BPP18) == DisplayColorModeBPP::BPP18);
+  }
+}
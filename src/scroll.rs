@@ -0,0 +1,212 @@
+use heapless::{
+  Deque,
+  String
+};
+
+// Bookkeeping of the widgets scrolling with the hardware vertical scroll
+// (Console, PhotoTicker). Slots are line positions in the frame memory
+// counted from the top of the scroll area, used as a ring
+
+// The last LINES lines of up to COLUMNS characters of a console. Each line
+// pushed goes in the ring slot after the previous one, so with the ring full
+// it takes the slot of the line dropped and the scroll start moves past it
+pub struct ConsoleLines<const LINES: usize, const COLUMNS: usize> {
+  lines : Deque<String<COLUMNS>, LINES>,
+  offset: usize // slot of the next line, the oldest one's with the ring full
+}
+
+impl<const LINES: usize, const COLUMNS: usize> Default for ConsoleLines<LINES, COLUMNS> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+#[allow(dead_code)]
+impl<const LINES: usize, const COLUMNS: usize> ConsoleLines<LINES, COLUMNS> {
+  pub fn new() -> Self {
+    Self {
+      lines : Deque::new(),
+      offset: 0
+    }
+  }
+
+  pub fn clear(&mut self) {
+    self.lines.clear();
+  }
+
+  pub fn is_full(&self) -> bool {
+    self.lines.is_full()
+  }
+
+  pub fn len(&self) -> usize {
+    self.lines.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.lines.is_empty()
+  }
+
+  // Oldest first
+  pub fn line(&self, index: usize) -> Option<&str> {
+    self.lines.iter().nth(index).map(|line| line.as_str())
+  }
+
+  // The scroll start, in lines, that puts the oldest line at the top
+  pub fn offset(&self) -> usize {
+    self.offset
+  }
+
+  // For the lines drawn again in order from slot 0 with the scroll start back
+  // there: the next one goes after the last
+  pub fn rewind(&mut self) {
+    self.offset = self.lines.len() % LINES.max(1);
+  }
+
+  // Cuts the line to COLUMNS characters and drops the oldest one when full.
+  // Returns the slot to draw it in, and whether a line was dropped so the
+  // scroll start has to move to offset(); None when LINES is 0
+  pub fn push(&mut self, text: &str) -> Option<(usize, bool)> {
+    if LINES == 0 {
+      return None;
+    }
+
+    let mut line: String<COLUMNS> = String::new();
+
+    for c in text.chars() {
+      if line.chars().count() >= COLUMNS || line.push(c).is_err() {
+        break;
+      }
+    }
+
+    let full = self.lines.is_full();
+
+    if full {
+      self.lines.pop_front();
+    }
+
+    self.lines.push_back(line).ok();
+
+    let slot = self.offset;
+
+    self.offset = (self.offset + 1) % LINES;
+
+    Some((slot, full))
+  }
+
+  // Slot showing the given screen row under the hardware scroll
+  pub fn row_slot(&self, row: usize) -> usize {
+    match self.lines.is_full() {
+      true  => (self.offset + row) % LINES,
+      false => row
+    }
+  }
+}
+
+// Where a ticker image stands: the scroll area is height lines and the image
+// image_height rows, repeating with no seam. Each line scrolling out at the
+// top gets the image row height rows further and comes back at the bottom
+#[derive(Clone, Copy)]
+pub struct TickerScroll {
+  height      : u16,
+  image_height: u16,
+  offset      : u16, // frame memory line at the top of the screen
+  position    : u16  // image row at the top of the screen
+}
+
+#[allow(dead_code)]
+impl TickerScroll {
+  // Both at least 1
+  pub fn new(height: u16, image_height: u16) -> Self {
+    Self {
+      height      : height.max(1),
+      image_height: image_height.max(1),
+      offset      : 0,
+      position    : 0
+    }
+  }
+
+  // The scroll start
+  pub fn offset(&self) -> u16 {
+    self.offset
+  }
+
+  pub fn position(&self) -> u16 {
+    self.position
+  }
+
+  pub fn restart(&mut self) {
+    self.offset = 0;
+    self.position = 0;
+  }
+
+  // Moves one line on, returning the line that scrolled out and the image row
+  // to draw in it; the scroll start goes to offset()
+  pub fn step(&mut self) -> (u16, u16) {
+    let line = self.offset;
+    let row = ((self.position as u32 + self.height as u32) % self.image_height as u32) as u16;
+
+    self.offset = (self.offset + 1) % self.height;
+    self.position = (self.position + 1) % self.image_height;
+
+    (line, row)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn console_ring_wraps_and_moves_the_scroll() {
+    let mut lines: ConsoleLines<3, 4> = ConsoleLines::new();
+
+    assert_eq!(lines.push("one"), Some((0, false)));
+    assert_eq!(lines.push("two"), Some((1, false)));
+    assert_eq!(lines.row_slot(1), 1);
+    assert_eq!(lines.push("three"), Some((2, false)));
+    assert_eq!(lines.offset(), 0);
+    assert_eq!(lines.push("four"), Some((0, true)));
+    assert_eq!(lines.offset(), 1);
+
+    // "two" is the oldest, at the top in slot 1, and "four" at the bottom
+    assert_eq!(lines.line(0), Some("two"));
+    assert_eq!(lines.line(2), Some("four"));
+    assert_eq!(lines.row_slot(0), 1);
+    assert_eq!(lines.row_slot(2), 0);
+
+    assert_eq!(lines.push("five"), Some((1, true)));
+    assert_eq!(lines.line(1), Some("four"));
+    assert_eq!(lines.row_slot(0), 2);
+
+    lines.rewind();
+
+    assert_eq!(lines.offset(), 0);
+    assert_eq!(lines.row_slot(0), 0);
+  }
+
+  #[test]
+  fn lines_are_cut_to_the_columns() {
+    let mut lines: ConsoleLines<2, 4> = ConsoleLines::new();
+
+    lines.push("abcdef");
+
+    assert_eq!(lines.line(0), Some("abcd"));
+  }
+
+  #[test]
+  fn ticker_offset_and_position_wrap() {
+    let mut scroll = TickerScroll::new(3, 5);
+
+    assert_eq!(scroll.step(), (0, 3));
+    assert_eq!(scroll.step(), (1, 4));
+    assert_eq!(scroll.step(), (2, 0));
+    assert_eq!((scroll.offset(), scroll.position()), (0, 3));
+    assert_eq!(scroll.step(), (0, 1));
+    assert_eq!(scroll.step(), (1, 2));
+    assert_eq!((scroll.offset(), scroll.position()), (2, 0));
+
+    scroll.restart();
+
+    assert_eq!(scroll.step(), (0, 3));
+  }
+}
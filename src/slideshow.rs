@@ -0,0 +1,133 @@
+// Cycles through a slice of photos skipping the excluded ones, which are only
+// reachable through show_hidden. Up to 32 photos can be excluded (indices 0-31)
+pub struct Slideshow<'a, T> {
+  current : Option<usize>,
+  excluded: u32,
+  photos  : &'a [T]
+}
+
+#[allow(dead_code)]
+impl<'a, T> Slideshow<'a, T> {
+  pub fn new(photos: &'a [T]) -> Self {
+    Self {
+      current : None,
+      excluded: 0,
+      photos
+    }
+  }
+
+  pub fn exclude(&mut self, index: usize) {
+    if index < 32 {
+      self.excluded |= 1 << index;
+    }
+  }
+
+  pub fn is_excluded(&self, index: usize) -> bool {
+    index < 32 && self.excluded & (1 << index) != 0
+  }
+
+  // Photo last returned by next/prev, None before the first call
+  pub fn current(&self) -> Option<&'a T> {
+    self.current.and_then(|index| self.photos.get(index))
+  }
+
+  pub fn current_index(&self) -> Option<usize> {
+    self.current
+  }
+
+  // The first call returns the last photo that isn't excluded
+  pub fn prev(&mut self) -> Option<&'a T> {
+    let count = self.photos.len();
+    let start = self.current.unwrap_or(0) + count;
+
+    self.step(|offset| (start - 1 - offset) % count.max(1))
+  }
+
+  // The first excluded photo; the position of next/prev is kept
+  pub fn show_hidden(&self) -> Option<&'a T> {
+    (0..self.photos.len())
+      .find(|&index| self.is_excluded(index))
+      .map(|index| &self.photos[index])
+  }
+
+  // Tries the candidate indices in order until one isn't excluded
+  fn step<F: Fn(usize) -> usize>(&mut self, candidate: F) -> Option<&'a T> {
+    for offset in 0..self.photos.len() {
+      let index = candidate(offset);
+
+      if !self.is_excluded(index) {
+        self.current = Some(index);
+
+        return self.photos.get(index);
+      }
+    }
+
+    None
+  }
+}
+
+// Infinite: after the last photo it starts over from the first one, so None
+// only comes with no photo left to show (all excluded, or none at all). Bound
+// it with take() or find() before collecting or looping over it
+impl<'a, T> Iterator for Slideshow<'a, T> {
+  type Item = &'a T;
+
+  // The first call returns the first photo that isn't excluded
+  fn next(&mut self) -> Option<&'a T> {
+    let count = self.photos.len();
+    let start = self.current.map(|index| index + 1).unwrap_or(0);
+
+    self.step(|offset| (start + offset) % count.max(1))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const PHOTOS: [char; 5] = ['a', 'b', 'c', 'd', 'e'];
+
+  #[test]
+  fn next_and_prev_wrap_around() {
+    let mut slideshow = Slideshow::new(&PHOTOS);
+
+    assert_eq!(slideshow.current(), None);
+    assert_eq!(slideshow.by_ref().take(6).collect::<Vec<_>>(), [&'a', &'b', &'c', &'d', &'e', &'a']);
+    assert_eq!(slideshow.prev(), Some(&'e'));
+    assert_eq!(slideshow.prev(), Some(&'d'));
+  }
+
+  #[test]
+  fn prev_first_returns_the_last_photo() {
+    let mut slideshow = Slideshow::new(&PHOTOS);
+
+    assert_eq!(slideshow.prev(), Some(&'e'));
+    assert_eq!(slideshow.current_index(), Some(4));
+  }
+
+  #[test]
+  fn excluded_photos_are_skipped_but_shown_hidden() {
+    let mut slideshow = Slideshow::new(&PHOTOS);
+
+    slideshow.exclude(0);
+    slideshow.exclude(3);
+
+    assert_eq!(slideshow.by_ref().take(4).collect::<Vec<_>>(), [&'b', &'c', &'e', &'b']);
+    assert_eq!(slideshow.prev(), Some(&'e'));
+    assert_eq!(slideshow.prev(), Some(&'c'));
+    assert_eq!(slideshow.show_hidden(), Some(&'a'));
+    assert_eq!(slideshow.current(), Some(&'c'));
+  }
+
+  #[test]
+  fn nothing_left_to_show_ends_it() {
+    let mut slideshow = Slideshow::new(&PHOTOS[..2]);
+
+    slideshow.exclude(0);
+    slideshow.exclude(1);
+
+    assert_eq!(slideshow.next(), None);
+    assert_eq!(slideshow.prev(), None);
+    assert_eq!(Slideshow::<char>::new(&[]).next(), None);
+  }
+}
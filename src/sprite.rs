@@ -0,0 +1,121 @@
+use crate::geometry::Rect;
+
+// Image of width * height pixels, row by row, already packed in the bytes of
+// the color mode it is drawn in (2 per pixel in BPP16, 3 in BPP18/BPP16M)
+#[derive(Clone, Copy)]
+pub struct Sprite<'a> {
+  pub data  : &'a [u8],
+  pub height: u16,
+  pub width : u16
+}
+
+#[allow(dead_code)]
+impl<'a> Sprite<'a> {
+  pub fn new(width: u16, height: u16, data: &'a [u8]) -> Self {
+    Self {
+      data,
+      height,
+      width
+    }
+  }
+
+  // Bytes of one row with bytes_per_pixel bytes a pixel, None when the sprite
+  // is empty or its data is too short to tile
+  pub fn row_bytes(&self, bytes_per_pixel: usize) -> Option<usize> {
+    let row_bytes = self.width as usize * bytes_per_pixel;
+
+    if row_bytes == 0 || self.height == 0 || self.data.len() < row_bytes * self.height as usize {
+      return None;
+    }
+
+    Some(row_bytes)
+  }
+
+  // Streams the region of the sprite repeated in both directions from (0, 0),
+  // handing buf to send each time it is full (whole pixels only) and once
+  // more with what is left. Does nothing for the sprites row_bytes rejects or
+  // a buf smaller than a pixel
+  pub fn stream_tiled<S: FnMut(&[u8])>(&self, region: Rect, bytes_per_pixel: usize, buf: &mut [u8], mut send: S) {
+    let tile_row_bytes = match self.row_bytes(bytes_per_pixel) {
+      Some(tile_row_bytes) => tile_row_bytes,
+      None                 => return
+    };
+
+    if buf.len() < bytes_per_pixel {
+      return;
+    }
+
+    let buffer_length = buf.len() - buf.len() % bytes_per_pixel;
+    let row_bytes = region.width as usize * bytes_per_pixel;
+    let column_offset = (region.x % self.width) as usize * bytes_per_pixel;
+    let mut length = 0usize;
+
+    for region_row in 0..region.height as usize {
+      let tile_row_start = (region.y as usize + region_row) % self.height as usize * tile_row_bytes;
+      let tile_row = &self.data[tile_row_start..tile_row_start + tile_row_bytes];
+      let mut sent = 0usize;
+
+      while sent < row_bytes {
+        if length == buffer_length {
+          send(&buf[0..length]);
+
+          length = 0;
+        }
+
+        let offset = (column_offset + sent) % tile_row_bytes;
+        let count = (tile_row_bytes - offset).min(row_bytes - sent).min(buffer_length - length);
+
+        buf[length..length + count].copy_from_slice(&tile_row[offset..offset + count]);
+
+        length += count;
+        sent += count;
+      }
+    }
+
+    if length > 0 {
+      send(&buf[0..length]);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const TILE: [u8; 6] = [
+    1, 2, 3,
+    4, 5, 6
+  ];
+
+  fn tiled(tile: &Sprite, column: u16, row: u16, width: u16, height: u16, buffer_size: usize) -> Vec<Vec<u8>> {
+    let mut sent = Vec::new();
+
+    tile.stream_tiled(Rect::new(column, row, width, height), 1, &mut vec![0; buffer_size], |bytes| sent.push(bytes.to_vec()));
+
+    sent
+  }
+
+  // A 3x2 tile over a 4x3 region: the last column and row are cut
+  #[test]
+  fn tiles_are_cut_at_the_region_edges() {
+    let sent = tiled(&Sprite::new(3, 2, &TILE), 0, 0, 4, 3, 64);
+
+    assert_eq!(sent, [vec![1, 2, 3, 1, 4, 5, 6, 4, 1, 2, 3, 1]]);
+  }
+
+  // fill_tiled clipped on the left and top keeps the phase of the origin
+  #[test]
+  fn clipped_tiles_keep_their_phase() {
+    let sent = tiled(&Sprite::new(3, 2, &TILE), 4, 1, 4, 2, 5);
+
+    assert_eq!(sent, [vec![5, 6, 4, 5, 2], vec![3, 1, 2]]);
+  }
+
+  #[test]
+  fn short_data_is_not_tiled() {
+    assert_eq!(Sprite::new(3, 3, &TILE).row_bytes(1), None);
+    assert_eq!(Sprite::new(0, 2, &TILE).row_bytes(1), None);
+    assert_eq!(Sprite::new(3, 2, &TILE).row_bytes(2), None);
+    assert_eq!(Sprite::new(3, 2, &TILE).row_bytes(1), Some(3));
+  }
+}
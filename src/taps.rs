@@ -0,0 +1,116 @@
+use crate::buttons::{
+  button_index,
+  JoystickButton,
+  JoystickStates,
+  BUTTONS
+};
+use crate::timing::elapsed;
+use heapless::Deque;
+
+// Presses remembered per button, also the highest tap count reported
+pub const TAP_HISTORY: usize = 4;
+
+const NO_TAPS: Deque<u32, TAP_HISTORY> = Deque::new();
+
+// Counts the presses of every button within a rolling window (double, triple
+// tap...). The window is in ticks (see timing)
+pub struct TapCounter {
+  presses: [Deque<u32, TAP_HISTORY>; 9],
+  window : u32
+}
+
+#[allow(dead_code)]
+impl TapCounter {
+  pub fn new(window: u32) -> Self {
+    Self {
+      presses: [NO_TAPS; 9],
+      window
+    }
+  }
+
+  // With joystick.states(), see Joystick::update
+  pub fn update(&mut self, states: &JoystickStates, now: u32) {
+    for button in BUTTONS {
+      let presses = &mut self.presses[button_index(button)];
+
+      while let Some(&press) = presses.front() {
+        if elapsed(now, press) <= self.window {
+          break;
+        }
+
+        presses.pop_front();
+      }
+
+      if states.just_pressed(button) {
+        if presses.is_full() {
+          presses.pop_front();
+        }
+
+        presses.push_back(now).ok();
+      }
+    }
+  }
+
+  // Presses of the button within the window before the last update (up to
+  // TAP_HISTORY)
+  pub fn tap_count(&self, button: JoystickButton) -> u8 {
+    self.presses[button_index(button)].len() as u8
+  }
+
+  // Forgets the presses of the button, e.g. once its gesture was handled so a
+  // fourth tap doesn't trigger the triple tap action again
+  pub fn reset(&mut self, button: JoystickButton) {
+    self.presses[button_index(button)].clear();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Taps of A at the given ticks, one update per tick from 0 to the last one
+  fn tap_a(counter: &mut TapCounter, taps: &[u32]) {
+    let mut states = JoystickStates::from_raw_state(0, 0);
+
+    for now in 0..=taps[taps.len() - 1] {
+      let state = match taps.contains(&now) {
+        true  => JoystickButton::A as u16,
+        false => 0
+      };
+
+      states.advance(state);
+      counter.update(&states, now);
+    }
+  }
+
+  #[test]
+  fn triple_tap_within_the_window() {
+    let mut counter = TapCounter::new(100);
+
+    tap_a(&mut counter, &[10, 40, 70]);
+
+    assert_eq!(counter.tap_count(JoystickButton::A), 3);
+    assert_eq!(counter.tap_count(JoystickButton::B), 0);
+
+    counter.reset(JoystickButton::A);
+
+    assert_eq!(counter.tap_count(JoystickButton::A), 0);
+  }
+
+  // The first tap is 100 ticks before the last update (in the window) or
+  // 101 (out of it)
+  #[test]
+  fn taps_straddling_the_window_boundary() {
+    let mut counter = TapCounter::new(100);
+
+    tap_a(&mut counter, &[10, 60, 110]);
+
+    assert_eq!(counter.tap_count(JoystickButton::A), 3);
+
+    let mut counter = TapCounter::new(100);
+
+    tap_a(&mut counter, &[10, 60, 111]);
+
+    assert_eq!(counter.tap_count(JoystickButton::A), 2);
+  }
+}
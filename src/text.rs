@@ -0,0 +1,673 @@
+// Layout of FONT text (glyph scale, text box, wrapping and character cells)
+// and its glyphs drawn on any DrawTarget. Cells are counted in columns and
+// rows of the font, a '\n' starting the next row back at column 0
+
+use crate::font::{
+  glyph_pixels,
+  FONT,
+  FONT_BASELINE,
+  FONT_CELL_SIZE
+};
+use crate::framebuffer::DrawTarget;
+use crate::geometry::Rect;
+
+// Largest text pixel size: a glyph (8 pixels) then spans the 320 lines of
+// the frame memory
+pub const DISPLAY_TEXT_MAX_PIXEL_SIZE: u16 = 40;
+
+// Thickest text outline, as wide as the largest text pixel
+pub const DISPLAY_TEXT_MAX_OUTLINE: u16 = DISPLAY_TEXT_MAX_PIXEL_SIZE;
+
+// Horizontal position of each line inside the draw_paragraph rect
+#[derive(Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub enum DisplayTextAlign {
+  Left,
+  Center,
+  Right
+}
+
+impl DisplayTextAlign {
+  // Left edge of a line line_width wide in the one at x, width wide
+  pub fn line_x(self, x: u16, width: u16, line_width: u16) -> u16 {
+    let space = width.saturating_sub(line_width);
+
+    match self {
+      DisplayTextAlign::Left   => x,
+      DisplayTextAlign::Center => x.saturating_add(space / 2),
+      DisplayTextAlign::Right  => x.saturating_add(space)
+    }
+  }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub enum DisplayTextAnchor {
+  TopLeft,      // (x, y) is the top-left corner of the first glyph
+  Center,       // (x, y) is the center of the text bounding box
+  BaselineLeft  // (x, y) is the left end of the first line baseline
+}
+
+#[derive(Clone, Copy, PartialEq)]
+#[allow(dead_code)]
+pub enum DisplayTextDirection {
+  LeftToRight, // glyphs advance rightwards from the left edge of the text box
+  RightToLeft  // glyphs advance leftwards from the right edge of the text box
+}
+
+// Screen pixels drawn for each FONT pixel, across and down. Both are clamped
+// to 1..=DISPLAY_TEXT_MAX_PIXEL_SIZE: a size of 0 would draw nothing at all
+// and huge ones overflow the glyph position math
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextScale {
+  pixel_height: u16,
+  pixel_width : u16
+}
+
+#[allow(dead_code)]
+impl TextScale {
+  pub fn new(pixel_width: u16, pixel_height: u16) -> Self {
+    Self {
+      pixel_height: pixel_height.clamp(1, DISPLAY_TEXT_MAX_PIXEL_SIZE),
+      pixel_width : pixel_width.clamp(1, DISPLAY_TEXT_MAX_PIXEL_SIZE)
+    }
+  }
+
+  // Same pixel width and height, keeping the 1:1 glyph aspect ratio
+  pub fn uniform(scale: u16) -> Self {
+    Self::new(scale, scale)
+  }
+
+  pub fn pixel_height(&self) -> u16 {
+    self.pixel_height
+  }
+
+  pub fn pixel_width(&self) -> u16 {
+    self.pixel_width
+  }
+
+  pub fn char_height(&self) -> u16 {
+    FONT_CELL_SIZE * self.pixel_height
+  }
+
+  pub fn char_width(&self) -> u16 {
+    FONT_CELL_SIZE * self.pixel_width
+  }
+
+  // Top-left corner of the FONT pixel at (column, row) of a glyph drawn at
+  // (x, y), None past the u16 coordinates
+  pub fn glyph_pixel(&self, x: u16, y: u16, column: u16, row: u16) -> Option<(u16, u16)> {
+    Some((x.checked_add(column * self.pixel_width)?, y.checked_add(row * self.pixel_height)?))
+  }
+
+  // Size of the text box: the longest line by the lines. Saturates at u16::MAX
+  // for text bigger than that
+  pub fn measure(&self, text: &str) -> (u16, u16) {
+    let columns = saturating_count(text.split('\n').map(|line| line.chars().count()).max().unwrap_or(0));
+    let rows = saturating_count(text.split('\n').count());
+
+    (columns.saturating_mul(self.char_width()), rows.saturating_mul(self.char_height()))
+  }
+
+  // Characters of a single line that fit in max_width pixels
+  pub fn fit(&self, text: &str, max_width: u16) -> usize {
+    text.chars().count().min((max_width / self.char_width()) as usize)
+  }
+
+  // Top-left corner of the text box placing the text at (x, y) according to
+  // the anchor, clamped to the screen origin
+  pub fn origin(&self, x: u16, y: u16, text: &str, anchor: DisplayTextAnchor) -> (u16, u16) {
+    match anchor {
+      DisplayTextAnchor::TopLeft => (x, y),
+      DisplayTextAnchor::Center => {
+        let (width, height) = self.measure(text);
+
+        (x.saturating_sub(width / 2), y.saturating_sub(height / 2))
+      },
+      DisplayTextAnchor::BaselineLeft => (x, y.saturating_sub(FONT_BASELINE * self.pixel_height))
+    }
+  }
+
+  // Inside of a label box, within its 1 pixel border if bordered, and the
+  // top-left corner of the text centered in it. The text is drawn clipped to
+  // the inside, so of a text bigger than that only the middle shows
+  pub fn label_layout(&self, rect: Rect, bordered: bool, text: &str) -> (Rect, (u16, u16)) {
+    let inner = match bordered {
+      true  => Rect::new(rect.x + 1, rect.y + 1, rect.width.saturating_sub(2), rect.height.saturating_sub(2)),
+      false => rect
+    };
+
+    (inner, self.origin(inner.x + inner.width / 2, inner.y + inner.height / 2, text, DisplayTextAnchor::Center))
+  }
+
+  // Left edge of each glyph of a line of the text box at x, box_width wide,
+  // in the order of the characters. Right to left the first character ends at
+  // the right edge of the box. The line stops at the first glyph starting
+  // past screen_width, or right to left before the screen origin
+  pub fn line_glyphs(self, x: u16, box_width: u16, line: &str, direction: DisplayTextDirection, screen_width: u16) -> impl Iterator<Item = (u16, char)> + '_ {
+    let char_width = self.char_width();
+    let right_to_left = direction == DisplayTextDirection::RightToLeft;
+
+    let mut render_x = match right_to_left {
+      true  => x.saturating_add(box_width),
+      false => x
+    };
+
+    line.chars().map_while(move |c| {
+      if right_to_left {
+        render_x = render_x.checked_sub(char_width)?;
+
+        return Some((render_x, c));
+      }
+
+      if render_x >= screen_width {
+        return None;
+      }
+
+      let glyph_x = render_x;
+
+      render_x = render_x.saturating_add(char_width);
+
+      Some((glyph_x, c))
+    })
+  }
+}
+
+// The text settings of a display. Colors are in the format of the display
+// color mode
+#[derive(Clone, Copy)]
+pub struct TextStyle {
+  pub background_color: Option<u32>,
+  pub direction       : DisplayTextDirection,
+  pub foreground_color: u32,
+  pub outline         : Option<(u32, u16)>, // color and thickness in screen pixels
+  pub scale           : TextScale
+}
+
+// Draws the FONT glyph at index with its top-left corner at (x, y), the pixels
+// not set in background_color if any. Glyphs out of FONT draw nothing
+pub fn draw_glyph<T: DrawTarget + ?Sized>(target: &mut T, x: u16, y: u16, index: usize, style: &TextStyle, background_color: Option<u32>) {
+  if FONT.get(index).is_none() {
+    return;
+  }
+
+  let pixel_width = style.scale.pixel_width();
+  let pixel_height = style.scale.pixel_height();
+
+  let mut background_color = background_color;
+
+  // The outline spills over the neighbour cells, so the background goes
+  // first as a whole and the outline of every pixel before any foreground
+  if let Some((outline_color, thickness)) = style.outline {
+    if let Some(background_color) = background_color.take() {
+      target.draw_solid_rect(x, y, style.scale.char_width(), style.scale.char_height(), background_color);
+    }
+
+    for (column, row, _) in glyph_pixels(index).filter(|&(_, _, set)| set) {
+      if let Some((render_x, render_y)) = style.scale.glyph_pixel(x, y, column, row) {
+        let outline_x = render_x.saturating_sub(thickness);
+        let outline_y = render_y.saturating_sub(thickness);
+        let outline_width = pixel_width.saturating_add(render_x - outline_x).saturating_add(thickness);
+        let outline_height = pixel_height.saturating_add(render_y - outline_y).saturating_add(thickness);
+
+        target.draw_solid_rect(outline_x, outline_y, outline_width, outline_height, outline_color);
+      }
+    }
+  }
+
+  for (column, row, set) in glyph_pixels(index) {
+    let (render_x, render_y) = match style.scale.glyph_pixel(x, y, column, row) {
+      Some(origin) => origin,
+      None         => continue
+    };
+
+    if set {
+      target.draw_solid_rect(render_x, render_y, pixel_width, pixel_height, style.foreground_color);
+    }
+    else if let Some(background_color) = background_color {
+      target.draw_solid_rect(render_x, render_y, pixel_width, pixel_height, background_color);
+    }
+  }
+}
+
+// Each byte is a FONT index as-is, laid left to right on a single line with
+// the background of the style under the whole run. Stops at the first glyph
+// starting past the target
+pub fn draw_bytes<T: DrawTarget + ?Sized>(target: &mut T, x: u16, y: u16, bytes: &[u8], style: &TextStyle) {
+  let char_width = style.scale.char_width();
+
+  if let Some(background_color) = style.background_color {
+    if !bytes.is_empty() {
+      target.draw_solid_rect(x, y, saturating_count(bytes.len()).saturating_mul(char_width), style.scale.char_height(), background_color);
+    }
+  }
+
+  let (width, _) = target.size();
+  let mut render_x = x;
+
+  for &byte in bytes {
+    if render_x >= width {
+      break;
+    }
+
+    draw_glyph(target, render_x, y, byte as usize, style, None);
+
+    render_x = render_x.saturating_add(char_width);
+  }
+}
+
+// Character or line count as u16, saturating for very long text
+pub fn saturating_count(count: usize) -> u16 {
+  count.min(u16::MAX as usize) as u16
+}
+
+// Longest start of the text up to columns characters that ends at a word
+// boundary, or the first columns characters when the first word is longer
+pub fn wrap_line(text: &str, columns: usize) -> &str {
+  let mut end = 0;
+  let mut last_break = None;
+
+  for (count, (index, c)) in text.char_indices().enumerate() {
+    if count == columns {
+      if c == ' ' {
+        return &text[..index];
+      }
+
+      return match last_break {
+        Some(last_break) => &text[..last_break],
+        None             => &text[..end]
+      };
+    }
+
+    if c == ' ' {
+      last_break = Some(index);
+    }
+
+    end = index + c.len_utf8();
+  }
+
+  text
+}
+
+// Lays a paragraph out in lines of up to columns characters: words wrap (a
+// word longer than a line is split) and '\n' starts a new line. Calls line
+// with the row and the line, spaces trimmed, for up to rows lines or until it
+// returns false; false when the text needed more
+pub fn wrap_paragraph<'a>(text: &'a str, columns: usize, rows: usize, mut line: impl FnMut(usize, &'a str) -> bool) -> bool {
+  let mut row = 0;
+
+  for paragraph in text.split('\n') {
+    let mut rest = paragraph;
+
+    loop {
+      if row == rows {
+        return false;
+      }
+
+      let wrapped = wrap_line(rest, columns);
+
+      rest = rest[wrapped.len()..].trim_start_matches(' ');
+
+      if !line(row, wrapped.trim_end_matches(' ')) {
+        return false;
+      }
+
+      row += 1;
+
+      if rest.is_empty() {
+        break;
+      }
+    }
+  }
+
+  true
+}
+
+// The (column, row, character) of every character, '\n' taking no cell
+pub fn text_cells(text: &str) -> impl Iterator<Item = (u16, u16, char)> + '_ {
+  let mut column = 0u16;
+  let mut row = 0u16;
+
+  text.chars().filter_map(move |c| {
+    if c == '\n' {
+      column = 0;
+      row += 1;
+
+      return None;
+    }
+
+    column += 1;
+
+    Some((column - 1, row, c))
+  })
+}
+
+pub fn cell_char(text: &str, column: u16, row: u16) -> Option<char> {
+  text_cells(text)
+    .find(|&(cell_column, cell_row, _)| cell_column == column && cell_row == row)
+    .map(|(_, _, c)| c)
+}
+
+// The cells to draw to turn shown into text: those whose character changed,
+// then a ' ' on the ones only shown had
+pub fn changed_cells<'a>(shown: &'a str, text: &'a str) -> impl Iterator<Item = (u16, u16, char)> + 'a {
+  let changed = text_cells(text).filter(move |&(column, row, c)| cell_char(shown, column, row) != Some(c));
+  let cleared = text_cells(shown)
+    .filter(move |&(column, row, _)| cell_char(text, column, row).is_none())
+    .map(|(column, row, _)| (column, row, ' '));
+
+  changed.chain(cleared)
+}
+
+// Reveals a text one character per next_cell, giving the cell to draw it in.
+// A '\n' is revealed along with the character after it, or with the last
+// character when nothing but '\n' follows, so every call until is_complete
+// gives exactly one cell
+#[derive(Clone, Copy)]
+pub struct Typewriter<'a> {
+  column  : u16,
+  revealed: usize, // bytes of text already revealed
+  row     : u16,
+  text    : &'a str
+}
+
+#[allow(dead_code)]
+impl<'a> Typewriter<'a> {
+  pub fn new(text: &'a str) -> Self {
+    Self {
+      column  : 0,
+      revealed: 0,
+      row     : 0,
+      text
+    }
+  }
+
+  pub fn is_complete(&self) -> bool {
+    self.revealed == self.text.len()
+  }
+
+  // The part of the text revealed so far
+  pub fn revealed(&self) -> &'a str {
+    &self.text[..self.revealed]
+  }
+
+  pub fn restart(&mut self) {
+    *self = Self::new(self.text);
+  }
+
+  // The (column, row, character) revealed, None once the whole text is
+  pub fn next_cell(&mut self) -> Option<(u16, u16, char)> {
+    for c in self.text[self.revealed..].chars() {
+      self.revealed += c.len_utf8();
+
+      if c == '\n' {
+        self.column = 0;
+        self.row += 1;
+
+        continue;
+      }
+
+      let cell = (self.column, self.row, c);
+
+      self.column += 1;
+
+      let rest = &self.text[self.revealed..];
+
+      if !rest.is_empty() && rest.bytes().all(|byte| byte == b'\n') {
+        self.column = 0;
+        self.row += saturating_count(rest.len());
+        self.revealed = self.text.len();
+      }
+
+      return Some(cell);
+    }
+
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::framebuffer::FrameBuffer;
+
+  fn style(outline: Option<(u32, u16)>) -> TextStyle {
+    TextStyle {
+      background_color: None,
+      direction       : DisplayTextDirection::LeftToRight,
+      foreground_color: 0xFFFF,
+      outline,
+      scale           : TextScale::uniform(1)
+    }
+  }
+
+  fn glyph_bit(index: usize, column: i32, row: i32) -> bool {
+    (0..8).contains(&column) && (0..8).contains(&row) && FONT[index][row as usize] & (0x80 >> column) != 0
+  }
+
+  #[test]
+  fn bytes_index_the_font_directly() {
+    let mut framebuffer: FrameBuffer<24, 8> = FrameBuffer::new();
+    // FONT has no glyphs past 0x7F, so 0xB2 is a blank cell that still
+    // moves the next glyph along
+    let bytes = [b'A', 0xB2, b'B'];
+    let style = TextStyle { background_color: Some(0x0101), ..style(None) };
+
+    draw_bytes(&mut framebuffer, 0, 0, &bytes, &style);
+
+    for (glyph, &byte) in bytes.iter().enumerate() {
+      for row in 0..8 {
+        for column in 0..8 {
+          let expected = match glyph_bit(byte as usize, column, row) {
+            true  => 0xFFFF,
+            false => 0x0101
+          };
+
+          assert_eq!(framebuffer.pixel((glyph as i32 * 8 + column) as u16, row as u16), expected);
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn bytes_stop_past_the_target() {
+    let mut framebuffer: FrameBuffer<24, 8> = FrameBuffer::new();
+
+    // The glyph pixels past u16::MAX are dropped, not wrapped around to 0
+    draw_bytes(&mut framebuffer, u16::MAX - 4, 0, b"ABC", &style(None));
+
+    assert_eq!(TextScale::uniform(1).glyph_pixel(u16::MAX - 4, 0, 7, 7), None);
+    assert!((0..24).all(|x| (0..8).all(|y| framebuffer.pixel(x, y) == 0)));
+  }
+
+  #[test]
+  fn outline_surrounds_the_glyph() {
+    let mut framebuffer: FrameBuffer<12, 12> = FrameBuffer::new();
+
+    draw_glyph(&mut framebuffer, 2, 2, 'A' as usize, &style(Some((0x1234, 1))), None);
+
+    for y in 0..12 {
+      for x in 0..12 {
+        let (column, row) = (x - 2, y - 2);
+        let near = (-1..=1).any(|dy| (-1..=1).any(|dx| glyph_bit('A' as usize, column + dx, row + dy)));
+
+        let expected = match (glyph_bit('A' as usize, column, row), near) {
+          (true, _)      => 0xFFFF,
+          (false, true)  => 0x1234,
+          (false, false) => 0
+        };
+
+        assert_eq!(framebuffer.pixel(x as u16, y as u16), expected, "pixel ({}, {})", x, y);
+      }
+    }
+  }
+
+  #[test]
+  fn thick_outline_at_the_screen_corners() {
+    let mut framebuffer: FrameBuffer<12, 12> = FrameBuffer::new();
+
+    draw_glyph(&mut framebuffer, 0, 0, 'A' as usize, &style(Some((0x1234, u16::MAX))), None);
+    draw_glyph(&mut framebuffer, u16::MAX - 8, u16::MAX - 8, 'A' as usize, &style(Some((0x1234, u16::MAX))), None);
+
+    assert_eq!(framebuffer.pixel(11, 11), 0x1234);
+  }
+
+  #[test]
+  fn pixel_sizes_are_clamped() {
+    let scale = TextScale::new(0, 1000);
+
+    assert_eq!((scale.pixel_width(), scale.pixel_height()), (1, DISPLAY_TEXT_MAX_PIXEL_SIZE));
+    assert_eq!((scale.char_width(), scale.char_height()), (8, 320));
+    assert_eq!(TextScale::new(3, 2).char_width(), 24);
+  }
+
+  #[test]
+  fn origin_for_each_anchor() {
+    let scale = TextScale::uniform(2);
+
+    // 3 x 2 characters of 16 x 16 pixels
+    assert_eq!(scale.measure("abc\nde"), (48, 32));
+    assert_eq!(scale.origin(100, 50, "abc\nde", DisplayTextAnchor::TopLeft), (100, 50));
+    assert_eq!(scale.origin(100, 50, "abc\nde", DisplayTextAnchor::Center), (76, 34));
+    assert_eq!(scale.origin(100, 50, "abc\nde", DisplayTextAnchor::BaselineLeft), (100, 36));
+    assert_eq!(scale.origin(10, 5, "abc\nde", DisplayTextAnchor::Center), (0, 0));
+  }
+
+  #[test]
+  fn right_to_left_glyph_positions() {
+    let scale = TextScale::uniform(1);
+    let (box_width, _) = scale.measure("abc");
+
+    assert!(scale.line_glyphs(10, box_width, "abc", DisplayTextDirection::RightToLeft, 240).eq([(26, 'a'), (18, 'b'), (10, 'c')]));
+    assert!(scale.line_glyphs(10, box_width, "abc", DisplayTextDirection::LeftToRight, 240).eq([(10, 'a'), (18, 'b'), (26, 'c')]));
+
+    // A shorter line of the box still starts at its right edge
+    assert!(scale.line_glyphs(10, box_width, "ab", DisplayTextDirection::RightToLeft, 240).eq([(26, 'a'), (18, 'b')]));
+
+    // Glyphs past the screen are left out
+    assert!(scale.line_glyphs(4, box_width, "abc", DisplayTextDirection::RightToLeft, 240).eq([(20, 'a'), (12, 'b'), (4, 'c')]));
+    assert!(scale.line_glyphs(0, 40, "abc", DisplayTextDirection::RightToLeft, 240).eq([(32, 'a'), (24, 'b'), (16, 'c')]));
+    assert!(scale.line_glyphs(0, 12, "abc", DisplayTextDirection::RightToLeft, 240).eq([(4, 'a')]));
+    assert!(scale.line_glyphs(230, box_width, "abc", DisplayTextDirection::LeftToRight, 240).eq([(230, 'a'), (238, 'b')]));
+  }
+
+  #[test]
+  fn partially_fitting_text() {
+    let scale = TextScale::uniform(2);
+
+    assert_eq!(scale.fit("Settings", 100), 6);
+    assert_eq!(scale.fit("Sett\u{e9}ngs", 95), 5);
+    assert_eq!(scale.fit("On", 100), 2);
+    assert_eq!(scale.fit("On", 15), 0);
+  }
+
+  fn paragraph_lines(text: &str, columns: usize, rows: usize) -> (Vec<&str>, bool) {
+    let mut lines = Vec::new();
+    let fits = wrap_paragraph(text, columns, rows, |row, line| {
+      assert_eq!(row, lines.len());
+
+      lines.push(line);
+
+      true
+    });
+
+    (lines, fits)
+  }
+
+  #[test]
+  fn overflowing_paragraph_does_not_fit() {
+    assert_eq!(paragraph_lines("the quick brown fox", 9, 2), (vec!["the quick", "brown fox"], true));
+    assert_eq!(paragraph_lines("the quick brown fox", 9, 1), (vec!["the quick"], false));
+    assert_eq!(paragraph_lines("jumps\n\nover", 9, 2), (vec!["jumps", ""], false));
+    assert_eq!(paragraph_lines("overlooked", 4, 3), (vec!["over", "look", "ed"], true));
+  }
+
+  // As draw_paragraph does once a row would start past u16::MAX
+  #[test]
+  fn paragraph_stops_when_a_line_returns_false() {
+    let mut lines = Vec::new();
+    let fits = wrap_paragraph("the quick brown fox", 9, 5, |row, line| {
+      lines.push(line);
+
+      row < 1
+    });
+
+    assert_eq!(lines, ["the quick", "brown fox"]);
+    assert!(!fits);
+  }
+
+  #[test]
+  fn lines_are_aligned_in_the_width() {
+    assert_eq!(DisplayTextAlign::Left.line_x(10, 100, 40), 10);
+    assert_eq!(DisplayTextAlign::Center.line_x(10, 100, 40), 40);
+    assert_eq!(DisplayTextAlign::Right.line_x(10, 100, 40), 70);
+  }
+
+  #[test]
+  fn long_label_text_is_clipped_to_the_inside() {
+    let scale = TextScale::uniform(1);
+    let (inner, (x, y)) = scale.label_layout(Rect::new(100, 100, 50, 20), true, "a long label");
+    let (width, height) = scale.measure("a long label");
+    let text_box = Rect::new(x, y, width, height);
+
+    assert_eq!(inner, Rect::new(101, 101, 48, 18));
+    assert_eq!((x, y), (77, 106));
+    assert_eq!(text_box.intersection(&inner), Some(Rect::new(101, 106, 48, 8)));
+
+    let (inner, (x, y)) = scale.label_layout(Rect::new(0, 0, 40, 10), false, "ok");
+
+    assert_eq!(inner, Rect::new(0, 0, 40, 10));
+    assert_eq!((x, y), (12, 1));
+  }
+
+  #[test]
+  fn scale_0_is_coerced_to_1() {
+    assert_eq!(TextScale::uniform(0), TextScale::new(1, 1));
+    assert_eq!(TextScale::uniform(0).char_height(), 8);
+    assert_eq!(TextScale::uniform(2).pixel_width(), 2);
+  }
+
+  #[test]
+  fn changing_number_redraws_only_changed_cells() {
+    assert!(changed_cells("FPS 59", "FPS 60").eq([(4, 0, '6'), (5, 0, '0')]));
+    assert!(changed_cells("FPS 60", "FPS 60").eq([]));
+    assert!(changed_cells("FPS 100", "FPS 99").eq([(4, 0, '9'), (5, 0, '9'), (6, 0, ' ')]));
+    assert!(changed_cells("FPS 9", "FPS 10").eq([(4, 0, '1'), (5, 0, '0')]));
+  }
+
+  #[test]
+  fn cells_follow_new_lines() {
+    assert!(text_cells("ab\nc").eq([(0, 0, 'a'), (1, 0, 'b'), (0, 1, 'c')]));
+    assert!(changed_cells("1\n22", "1\n2").eq([(1, 1, ' ')]));
+  }
+
+  #[test]
+  fn typewriter_reveals_one_glyph_per_tick() {
+    let mut typewriter = Typewriter::new("a\n\u{e9}c");
+
+    assert_eq!(typewriter.next_cell(), Some((0, 0, 'a')));
+    assert_eq!(typewriter.revealed(), "a");
+    assert_eq!(typewriter.next_cell(), Some((0, 1, '\u{e9}')));
+    assert_eq!(typewriter.revealed(), "a\n\u{e9}");
+    assert!(!typewriter.is_complete());
+    assert_eq!(typewriter.next_cell(), Some((1, 1, 'c')));
+    assert!(typewriter.is_complete());
+    assert_eq!(typewriter.next_cell(), None);
+
+    typewriter.restart();
+
+    assert_eq!(typewriter.revealed(), "");
+    assert_eq!(typewriter.next_cell(), Some((0, 0, 'a')));
+  }
+
+  #[test]
+  fn trailing_line_break_comes_with_the_last_glyph() {
+    let mut typewriter = Typewriter::new("ab\n\n");
+
+    assert_eq!(typewriter.next_cell(), Some((0, 0, 'a')));
+    assert_eq!(typewriter.next_cell(), Some((1, 0, 'b')));
+    assert!(typewriter.is_complete());
+    assert_eq!(typewriter.revealed(), "ab\n\n");
+    assert_eq!(typewriter.next_cell(), None);
+  }
+}
@@ -0,0 +1,76 @@
+use crate::display::{
+  Display,
+  DisplayError,
+  DISPLAY_RAM_LINES
+};
+use crate::scroll::TickerScroll;
+
+// Attract mode loop: scrolls a tall image (screen width * image height pixels
+// in the active color mode, at least one screen tall) up through the screen
+// with the hardware vertical scroll, starting over from its first row after
+// the last one with no seam. The scroll area is the screen height, and each
+// line that scrolls out at the top gets the next image row and comes back in
+// at the bottom, so only the new rows are sent. Like any vertical scroll it
+// needs a portrait rotation and the display without a framebuffer
+pub struct PhotoTicker<'a> {
+  image    : &'a [u8],
+  row_bytes: usize,
+  scroll   : TickerScroll
+}
+
+#[allow(dead_code)]
+impl<'a> PhotoTicker<'a> {
+  pub fn new<const BUFFER_SIZE: usize>(display: &Display<BUFFER_SIZE>, image: &'a [u8]) -> Result<Self, DisplayError> {
+    let (width, height) = display.resolution();
+    let row_bytes = width as usize * display.bytes_per_pixel();
+    let needed = row_bytes * height as usize;
+
+    if row_bytes == 0 || image.len() < needed {
+      return Err(DisplayError::BufferTooSmall { needed, length: image.len() });
+    }
+
+    let image_height = (image.len() / row_bytes).min(u16::MAX as usize) as u16;
+
+    Ok(Self {
+      image,
+      row_bytes,
+      scroll   : TickerScroll::new(height, image_height)
+    })
+  }
+
+  pub fn position(&self) -> u16 {
+    self.scroll.position()
+  }
+
+  // Sets the scroll area and draws the first screen of the image
+  pub fn start<const BUFFER_SIZE: usize>(&mut self, display: &mut Display<BUFFER_SIZE>) {
+    let (width, height) = display.resolution();
+
+    self.scroll.restart();
+
+    display.set_scroll_area(0, height);
+    display.set_scroll_start(0);
+
+    let _ = display.draw_image(0, 0, width, height, &self.image[..height as usize * self.row_bytes]);
+  }
+
+  // Moves the image up by lines rows
+  pub fn advance<const BUFFER_SIZE: usize>(&mut self, display: &mut Display<BUFFER_SIZE>, lines: u16) {
+    let (width, height) = display.resolution();
+
+    for _ in 0..lines.min(height) {
+      let (line, row) = self.scroll.step();
+      let row = row as usize;
+
+      display.set_scroll_start(self.scroll.offset());
+
+      let _ = display.draw_image(0, line, width, 1, &self.image[row * self.row_bytes..(row + 1) * self.row_bytes]);
+    }
+  }
+
+  // Puts the scroll area back to the whole frame memory
+  pub fn stop<const BUFFER_SIZE: usize>(&mut self, display: &mut Display<BUFFER_SIZE>) {
+    display.set_scroll_area(0, DISPLAY_RAM_LINES);
+    display.set_scroll_start(0);
+  }
+}
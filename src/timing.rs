@@ -0,0 +1,82 @@
+// Ticks: every `now` taken by the timing helpers (FpsCounter, Animator,
+// IdleDimmer, TapCounter...) is a free running u32 counter, e.g. the lower
+// word of the RP2040 timer (microseconds), and their durations are in the
+// same ticks. Time spans are measured with elapsed, so the counter wrapping
+// around is handled
+
+// Ticks from since to now, right across a counter wrap
+pub fn elapsed(now: u32, since: u32) -> u32 {
+  now.wrapping_sub(since)
+}
+
+// Frames per second over one second windows, with `now` in microseconds
+pub struct FpsCounter {
+  count       : u32,
+  fps         : u32,
+  window_start: u32
+}
+
+#[allow(dead_code)]
+impl FpsCounter {
+  pub fn new(now: u32) -> Self {
+    Self {
+      count       : 0,
+      fps         : 0,
+      window_start: now
+    }
+  }
+
+  // Frames counted in the last complete window
+  pub fn fps(&self) -> u32 {
+    self.fps
+  }
+
+  // Call once per frame; returns true when a window just completed and fps()
+  // has a new value
+  pub fn tick(&mut self, now: u32) -> bool {
+    if elapsed(now, self.window_start) >= 1_000_000 {
+      self.fps = self.count;
+      self.count = 1;
+      self.window_start = now;
+
+      return true;
+    }
+
+    self.count += 1;
+
+    false
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // 30 frames 1/30s apart, the first one when the counter starts; the next
+  // frame completes the second and counts towards the following one
+  #[test]
+  fn counts_the_frames_of_one_second() {
+    let mut counter = FpsCounter::new(0);
+
+    for frame in 0..30 {
+      assert!(!counter.tick(frame * 33_334));
+    }
+
+    assert!(counter.tick(1_000_020));
+    assert_eq!(counter.fps(), 30);
+  }
+
+  #[test]
+  fn window_across_a_tick_wrap() {
+    let start = u32::MAX - 400_000;
+    let mut counter = FpsCounter::new(start);
+
+    for frame in 0..10 {
+      assert!(!counter.tick(start.wrapping_add(frame * 100_000)));
+    }
+
+    assert!(counter.tick(start.wrapping_add(1_000_000)));
+    assert_eq!(counter.fps(), 10);
+    assert_eq!(elapsed(599_999, start), 1_000_000);
+  }
+}
@@ -0,0 +1,87 @@
+// sin(0..=90 degrees) scaled by 16384, enough precision for shapes the size of
+// the screen without float support
+const SINE: [i32; 91] = [
+      0,   286,   572,   857,  1143,  1428,  1713,  1997,  2280,  2563,
+   2845,  3126,  3406,  3686,  3964,  4240,  4516,  4790,  5063,  5334,
+   5604,  5872,  6138,  6402,  6664,  6924,  7182,  7438,  7692,  7943,
+   8192,  8438,  8682,  8923,  9162,  9397,  9630,  9860, 10087, 10311,
+  10531, 10749, 10963, 11174, 11381, 11585, 11786, 11982, 12176, 12365,
+  12551, 12733, 12911, 13085, 13255, 13421, 13583, 13741, 13894, 14044,
+  14189, 14330, 14466, 14598, 14726, 14849, 14968, 15082, 15191, 15296,
+  15396, 15491, 15582, 15668, 15749, 15826, 15897, 15964, 16026, 16083,
+  16135, 16182, 16225, 16262, 16294, 16322, 16344, 16362, 16374, 16382,
+  16384
+];
+
+// Angles are in degrees, 0 pointing right and growing clockwise on screen
+pub fn sin_deg(degrees: i32) -> i32 {
+  let degrees = degrees.rem_euclid(360);
+
+  match degrees {
+    0..=90    =>  SINE[degrees as usize],
+    91..=180  =>  SINE[(180 - degrees) as usize],
+    181..=270 => -SINE[(degrees - 180) as usize],
+    _         => -SINE[(360 - degrees) as usize]
+  }
+}
+
+pub fn cos_deg(degrees: i32) -> i32 {
+  sin_deg(degrees + 90)
+}
+
+// Whether the offset (dx, dy) from a circle center lies in the sector of the
+// given radius going clockwise from start_degrees to end_degrees. Equal angles
+// are an empty sector, unless they are a full turn apart (0 to 360)
+pub fn sector_contains(dx: i32, dy: i32, radius: u16, start_degrees: i32, end_degrees: i32) -> bool {
+  let (dx, dy, radius) = (dx as i64, dy as i64, radius as i64);
+
+  if dx * dx + dy * dy > radius * radius {
+    return false;
+  }
+
+  let sweep = (end_degrees - start_degrees).rem_euclid(360);
+
+  if sweep == 0 {
+    return end_degrees != start_degrees;
+  }
+
+  // Cross products are positive when the point is clockwise from the edge
+  let after_start = cos_deg(start_degrees) as i64 * dy - sin_deg(start_degrees) as i64 * dx >= 0;
+  let before_end = cos_deg(end_degrees) as i64 * dy - sin_deg(end_degrees) as i64 * dx <= 0;
+
+  if sweep <= 180 {
+    after_start && before_end
+  }
+  else {
+    after_start || before_end
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // 0 to 90 degrees is the bottom right quarter, y growing downwards
+  #[test]
+  fn quarter_slice_includes_only_its_quadrant() {
+    assert!(sector_contains(5, 5, 10, 0, 90));
+    assert!(sector_contains(9, 0, 10, 0, 90));
+    assert!(sector_contains(0, 9, 10, 0, 90));
+
+    assert!(!sector_contains(-5, 5, 10, 0, 90));
+    assert!(!sector_contains(5, -5, 10, 0, 90));
+    assert!(!sector_contains(-5, -5, 10, 0, 90));
+    assert!(!sector_contains(8, 8, 10, 0, 90));
+  }
+
+  #[test]
+  fn quarter_slice_wrapping_past_0() {
+    assert!(sector_contains(5, 0, 10, 315, 45));
+    assert!(sector_contains(5, -4, 10, 315, 45));
+    assert!(sector_contains(5, 4, 10, 315, 45));
+
+    assert!(!sector_contains(0, 5, 10, 315, 45));
+    assert!(!sector_contains(0, -5, 10, 315, 45));
+    assert!(!sector_contains(-5, 0, 10, 315, 45));
+  }
+}
@@ -0,0 +1,262 @@
+use crate::dial::{
+  gauge_angle,
+  SpinnerRing,
+  GAUGE_START_DEGREES,
+  GAUGE_SWEEP_DEGREES
+};
+use crate::display::{
+  Display,
+  DisplayTextAnchor
+};
+use crate::fmt::{
+  format_u32_into,
+  U32_MAX_LENGTH
+};
+use crate::geometry::Rect;
+use crate::text::{
+  changed_cells,
+  text_cells,
+  Typewriter
+};
+use heapless::String;
+
+// Widgets keep what they last drew to repaint only what changed; their
+// invalidate forces a full redraw on the next draw, for when something was
+// drawn over them
+
+// Text that is redrawn often in the same place (FPS counter, clock). Only the
+// cells whose character changed are redrawn and cells left over from a longer
+// previous text are cleared, so it needs an opaque text background color
+pub struct StatusLine<const N: usize> {
+  shown: String<N>,
+  valid: bool,
+  x    : u16,
+  y    : u16
+}
+
+#[allow(dead_code)]
+impl<const N: usize> StatusLine<N> {
+  pub fn new(x: u16, y: u16) -> Self {
+    Self {
+      shown: String::new(),
+      valid: false,
+      x,
+      y
+    }
+  }
+
+  pub fn invalidate(&mut self) {
+    self.valid = false;
+  }
+
+  pub fn draw<const BUFFER_SIZE: usize>(&mut self, display: &mut Display<BUFFER_SIZE>, text: &str) {
+    let char_width = display.char_width();
+    let char_height = display.char_height();
+    let (x, y) = (self.x, self.y);
+
+    let mut draw_cell = |(column, row, c): (u16, u16, char)| {
+      display.draw_char(x + column * char_width, y + row * char_height, c);
+    };
+
+    if self.valid {
+      changed_cells(self.shown.as_str(), text).for_each(&mut draw_cell);
+    }
+    else {
+      text_cells(text).for_each(&mut draw_cell);
+    }
+
+    self.shown.clear();
+
+    self.valid = self.shown.push_str(text).is_ok();
+  }
+}
+
+// Activity indicator: a circle split in segments with the lit ones moving
+// clockwise one segment per tick. draw paints it whole, tick only repaints the
+// segment turning off and the one turning on
+pub struct Spinner {
+  background_color: u32,
+  center_x        : u16,
+  center_y        : u16,
+  color           : u32,
+  radius          : u16,
+  ring            : SpinnerRing
+}
+
+#[allow(dead_code)]
+impl Spinner {
+  // segments is clamped to 1..=SPINNER_MAX_SEGMENTS, as SpinnerRing does
+  pub fn new(center_x: u16, center_y: u16, radius: u16, segments: u16, lit: u16, color: u32, background_color: u32) -> Self {
+    Self {
+      background_color,
+      center_x,
+      center_y,
+      color,
+      radius,
+      ring            : SpinnerRing::new(segments, lit)
+    }
+  }
+
+  // Clockwise from 0 degrees pointing right, where the lit segments start
+  pub fn angle(&self) -> i32 {
+    self.ring.angle()
+  }
+
+  pub fn draw<const BUFFER_SIZE: usize>(&self, display: &mut Display<BUFFER_SIZE>) {
+    for segment in 0..self.ring.segments() {
+      let color = match self.ring.is_lit(segment) {
+        true  => self.color,
+        false => self.background_color
+      };
+
+      self.draw_segment(display, segment, color);
+    }
+  }
+
+  pub fn tick<const BUFFER_SIZE: usize>(&mut self, display: &mut Display<BUFFER_SIZE>) {
+    if let Some((off, on)) = self.ring.advance() {
+      self.draw_segment(display, off, self.background_color);
+      self.draw_segment(display, on, self.color);
+    }
+  }
+
+  fn draw_segment<const BUFFER_SIZE: usize>(&self, display: &mut Display<BUFFER_SIZE>, segment: u16, color: u32) {
+    display.fill_pie(self.center_x, self.center_y, self.radius, self.ring.segment_degrees(segment), self.ring.segment_degrees(segment + 1), color);
+  }
+}
+
+// Dial going clockwise from the bottom left (135 degrees) to the bottom right
+// (405), filled up to the value, with the value written in the middle in the
+// current text settings. After the first draw only the slice between the old
+// and the new value is repainted, and the readout over the dial repainted
+// where the previous one was, so the readout must fit inside the dial
+pub struct Gauge {
+  angle      : Option<i32>, // end of the filled part as last drawn
+  center_x   : u16,
+  center_y   : u16,
+  color      : u32,
+  max        : u32,
+  min        : u32,
+  radius     : u16,
+  readout    : Option<Rect>, // area of the readout as last drawn
+  track_color: u32
+}
+
+#[allow(dead_code)]
+impl Gauge {
+  pub fn new(center_x: u16, center_y: u16, radius: u16, min: u32, max: u32, color: u32, track_color: u32) -> Self {
+    Self {
+      angle      : None,
+      center_x,
+      center_y,
+      color,
+      max        : max.max(min),
+      min,
+      radius,
+      readout    : None,
+      track_color
+    }
+  }
+
+  // Where the filled part of the dial ends for the value (clamped to min..=max)
+  pub fn angle_for(&self, value: u32) -> i32 {
+    gauge_angle(value, self.min, self.max)
+  }
+
+  pub fn invalidate(&mut self) {
+    self.angle = None;
+    self.readout = None;
+  }
+
+  pub fn draw<const BUFFER_SIZE: usize>(&mut self, display: &mut Display<BUFFER_SIZE>, value: u32) {
+    let angle = self.angle_for(value);
+    let end = GAUGE_START_DEGREES + GAUGE_SWEEP_DEGREES;
+
+    match self.angle {
+      None => {
+        display.fill_pie(self.center_x, self.center_y, self.radius, GAUGE_START_DEGREES, angle, self.color);
+        display.fill_pie(self.center_x, self.center_y, self.radius, angle, end, self.track_color);
+      },
+      Some(previous) if angle > previous => {
+        display.fill_pie(self.center_x, self.center_y, self.radius, previous, angle, self.color);
+      },
+      Some(previous) if angle < previous => {
+        display.fill_pie(self.center_x, self.center_y, self.radius, angle, previous, self.track_color);
+      },
+      Some(_) => ()
+    }
+
+    let redrawn = self.angle.is_none();
+
+    self.angle = Some(angle);
+
+    let mut buf = [0u8; U32_MAX_LENGTH];
+    let text = format_u32_into(&mut buf, value);
+
+    let (x, y) = display.text_origin(self.center_x, self.center_y, text, DisplayTextAnchor::Center);
+    let (width, height) = display.measure_text(text);
+
+    // A shorter readout would leave the edges of the old one, and without a
+    // text background the glyphs would pile up. Under it both parts of the
+    // dial are painted again, as the slices above only cover what changed
+    if let Some(readout) = self.readout {
+      if !redrawn && display.push_clip(readout).is_ok() {
+        display.fill_pie(self.center_x, self.center_y, self.radius, GAUGE_START_DEGREES, angle, self.color);
+        display.fill_pie(self.center_x, self.center_y, self.radius, angle, end, self.track_color);
+
+        display.pop_clip();
+      }
+    }
+
+    display.draw_str(x, y, text);
+
+    self.readout = Some(Rect::new(x, y, width, height));
+  }
+}
+
+// Intro text revealed one character per tick, each tick drawing only the new
+// glyph with the current text settings. A '\n' starts the next line under the
+// first character and is revealed along with the character after it, so every
+// tick until the end draws exactly one glyph
+pub struct TypewriterText<'a> {
+  typewriter: Typewriter<'a>,
+  x         : u16,
+  y         : u16
+}
+
+#[allow(dead_code)]
+impl<'a> TypewriterText<'a> {
+  pub fn new(x: u16, y: u16, text: &'a str) -> Self {
+    Self {
+      typewriter: Typewriter::new(text),
+      x,
+      y
+    }
+  }
+
+  pub fn is_complete(&self) -> bool {
+    self.typewriter.is_complete()
+  }
+
+  // The part of the text shown so far
+  pub fn revealed(&self) -> &'a str {
+    self.typewriter.revealed()
+  }
+
+  // Starts over from an empty text; what was drawn stays on screen
+  pub fn restart(&mut self) {
+    self.typewriter.restart();
+  }
+
+  // Draws the next character, false once the whole text is revealed
+  pub fn tick<const BUFFER_SIZE: usize>(&mut self, display: &mut Display<BUFFER_SIZE>) -> bool {
+    match self.typewriter.next_cell() {
+      Some((column, row, c)) => {
+        display.draw_char(self.x + column * display.char_width(), self.y + row * display.char_height(), c);
+
+        true
+      },
+      None => false
+    }
+  }
+}